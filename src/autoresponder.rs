@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::scenario::json_contains;
+use crate::structs::{AutoResponder, MatchKind};
+
+/// The compiled pattern of an `AutoResponder`.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Contains(String),
+    Regex(Regex),
+    Json(Value),
+}
+
+fn compile(responder: &AutoResponder) -> Result<Pattern> {
+    Ok(match responder.kind {
+        MatchKind::Contains => Pattern::Contains(responder.pattern.clone()),
+        MatchKind::Regex => Pattern::Regex(Regex::new(&responder.pattern)?),
+        MatchKind::Json => Pattern::Json(serde_json::from_str(&responder.pattern)?),
+    })
+}
+
+/// Checks that the responder's pattern compiles, so broken rules are rejected when added, and
+/// keeps the compiled pattern with the responder.
+pub fn validate_responder(responder: &AutoResponder) -> Result<()> {
+    let pattern = compile(responder)?;
+    let _ = responder.compiled.set(Some(pattern));
+    Ok(())
+}
+
+/// Returns the variables to render the response with when `frame` matches the responder's
+/// pattern, or `None` when it does not. Named groups of a regex pattern become variables.
+pub fn match_frame(responder: &AutoResponder, frame: &str) -> Option<HashMap<String, String>> {
+    let pattern = responder.compiled.get_or_init(|| compile(responder).ok());
+    match pattern.as_ref()? {
+        Pattern::Contains(text) => frame.contains(text.as_str()).then(HashMap::new),
+        Pattern::Regex(re) => {
+            let caps = re.captures(frame)?;
+            Some(
+                re.capture_names()
+                    .flatten()
+                    .filter_map(|name| {
+                        Some((name.to_string(), caps.name(name)?.as_str().to_string()))
+                    })
+                    .collect(),
+            )
+        }
+        Pattern::Json(expected) => {
+            let actual: Value = serde_json::from_str(frame).ok()?;
+            json_contains(&actual, expected).then(HashMap::new)
+        }
+    }
+}
@@ -0,0 +1,1431 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{Local, Utc};
+use clap::{Args, Parser, Subcommand};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::time::{timeout_at, Instant};
+
+use crate::environment::{self, mask, masked_hex, MASK};
+use crate::extraction::extract;
+use crate::golden::validate_pointer;
+use crate::networking::network_processor;
+use crate::persistence::{get_stored_app, store_app};
+use crate::recording::{load_recording, RecordedEvent, Recorder, ReplayReport, ReplaySpeed};
+use crate::report::{write_junit, write_tap, TestCase};
+use crate::scenario::{parse_scenario, StepResult};
+use crate::structs::{
+    AppState, ConnectionProfile, Environment, ExtractionRule, GoldenRecording, Message, SnippetKind,
+};
+use crate::templating::{render_template, render_url, TemplateContext};
+use crate::triage::is_crash;
+use crate::utils::{is_valid_websocket_ip, parse_hex, random_payload};
+use crate::workspace::{add_workspace, export_workspace, import_workspace};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const TRIAGE_TIMEOUT: Duration = Duration::from_secs(600);
+// Time left for close frames and late replies before a command exits
+const GRACE_PERIOD: Duration = Duration::from_millis(500);
+// Last frames of each connection attached to reports
+const REPORTED_FRAMES: usize = 20;
+
+#[derive(Parser)]
+#[command(
+    name = "rust-socket-sandbox",
+    version,
+    about = "WebSocket sandbox. Starts the GUI when no command is given."
+)]
+pub struct Cli {
+    /// Environment read by `{{env.NAME}}` instead of the active one
+    #[arg(long, global = true)]
+    pub env: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Connect, send messages and print every event until the duration elapses
+    Connect {
+        /// WebSocket URL, the id of a saved connection or a profile name
+        target: String,
+        /// Message to send once connected, can be repeated. Templates are expanded
+        #[arg(long)]
+        send: Vec<String>,
+        /// Rhai script to attach instead of the saved connection's or profile's one
+        #[arg(long)]
+        script: Option<PathBuf>,
+        /// Seconds to stay connected
+        #[arg(long, default_value_t = 5)]
+        duration: u64,
+        /// Record the session to this file
+        #[arg(long)]
+        record: Option<PathBuf>,
+    },
+    /// Run a scenario file on one or more connections
+    RunScenario {
+        file: PathBuf,
+        /// URL, saved connection id or profile used by `connect` steps without a URL
+        #[arg(long)]
+        target: Option<String>,
+        /// Number of connections running the scenario concurrently
+        #[arg(long, default_value_t = 1)]
+        connections: u8,
+        #[command(flatten)]
+        report: ReportArgs,
+    },
+    /// Open many connections and send a templated message on each of them periodically
+    Load {
+        /// WebSocket URL, the id of a saved connection or a profile name
+        target: String,
+        #[arg(long, default_value_t = 10)]
+        connections: u8,
+        #[arg(long, default_value = "{{counter}}")]
+        message: String,
+        /// Milliseconds between sends
+        #[arg(long, default_value_t = 1000)]
+        interval: u64,
+        /// Messages sent per connection
+        #[arg(long, default_value_t = 10)]
+        count: u32,
+        #[command(flatten)]
+        report: ReportArgs,
+        /// Record the session to this file
+        #[arg(long)]
+        record: Option<PathBuf>,
+    },
+    /// Interactive session: lines read from stdin are sent, incoming frames are printed
+    Repl {
+        /// WebSocket URL, the id of a saved connection or a profile name
+        target: String,
+        /// Record the session to this file
+        #[arg(long)]
+        record: Option<PathBuf>,
+    },
+    /// Replay the client side of a recorded session and diff the server's responses
+    Replay {
+        file: PathBuf,
+        /// URL, saved connection id or profile replacing the recorded URLs
+        #[arg(long)]
+        target: Option<String>,
+        /// Speed relative to the recording, e.g. 2 for twice as fast
+        #[arg(long, conflicts_with = "fast")]
+        speed: Option<f64>,
+        /// Send every frame as soon as the responses recorded before it arrived
+        #[arg(long)]
+        fast: bool,
+        /// JSON pointer of a volatile field left out of the comparison, can be repeated
+        #[arg(long)]
+        ignore: Vec<String>,
+    },
+    /// Manage golden recordings and check a server against them
+    Golden {
+        #[command(subcommand)]
+        command: GoldenCommand,
+    },
+    /// Manage environments and their variables, see `--env`
+    Environment {
+        #[command(subcommand)]
+        command: EnvironmentCommand,
+    },
+    /// Manage workspaces of connection profiles
+    Workspace {
+        #[command(subcommand)]
+        command: WorkspaceCommand,
+    },
+    /// Send random payloads and triage the ones that make the server drop the connection
+    Fuzz {
+        /// WebSocket URL, the id of a saved connection or a profile name
+        target: String,
+        #[arg(long, default_value_t = 100)]
+        iterations: u32,
+        #[arg(long, default_value_t = 1)]
+        min_len: usize,
+        #[arg(long, default_value_t = 256)]
+        max_len: usize,
+        /// Milliseconds to wait for a disconnect after each payload
+        #[arg(long, default_value_t = 100)]
+        interval: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum GoldenCommand {
+    /// Mark a recording as golden
+    Add {
+        file: PathBuf,
+        /// Defaults to the file name
+        #[arg(long)]
+        name: Option<String>,
+        /// JSON pointer of a volatile field left out of the comparison, can be repeated.
+        /// `*` matches any key or index, e.g. /items/*/id
+        #[arg(long)]
+        ignore: Vec<String>,
+    },
+    /// Forget a golden recording, the file is kept
+    Remove {
+        name: String,
+    },
+    List,
+    /// Replay golden recordings and report the server responses that changed
+    Check {
+        /// Golden recording to check, all of them when omitted
+        name: Option<String>,
+        /// URL, saved connection id or profile replacing the recorded URLs
+        #[arg(long)]
+        target: Option<String>,
+        /// Speed relative to the recording. As fast as possible when omitted
+        #[arg(long)]
+        speed: Option<f64>,
+        #[command(flatten)]
+        report: ReportArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum WorkspaceCommand {
+    /// List the workspaces and their profiles, the active one is marked with *
+    List,
+    /// Make a workspace the active one, creating it if needed
+    Use { name: String },
+    /// Save a connection's settings as a profile of the active workspace
+    Save {
+        /// Id of the saved connection
+        id: u8,
+        name: String,
+    },
+    /// Delete a workspace or, given as `workspace/profile`, one of its profiles
+    Remove { name: String },
+    /// Write a workspace to a file to share it
+    Export {
+        /// Defaults to the active workspace
+        #[arg(long)]
+        name: Option<String>,
+        file: PathBuf,
+    },
+    /// Add a workspace from an exported file, replacing the one with the same name
+    Import { file: PathBuf },
+}
+
+#[derive(Subcommand)]
+pub enum EnvironmentCommand {
+    /// List the environments and their variables, the active one is marked with *
+    List,
+    /// Make an environment the active one, creating it if needed
+    Use {
+        name: String,
+    },
+    /// Set a variable of the active environment, or of the one given with `--env`
+    Set {
+        name: String,
+        /// Read from stdin when omitted, which keeps secrets out of the shell history
+        value: Option<String>,
+        /// Keep the value in the secret store and mask it in output
+        #[arg(long)]
+        secret: bool,
+    },
+    Unset {
+        name: String,
+    },
+    /// Delete an environment with its variables and secrets
+    Delete {
+        name: String,
+    },
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    /// Write the results as a JUnit XML report to this path
+    #[arg(long)]
+    junit: Option<PathBuf>,
+    /// Write the results as a TAP report to this path
+    #[arg(long)]
+    tap: Option<PathBuf>,
+}
+
+impl ReportArgs {
+    fn write(&self, name: &str, cases: &[TestCase]) -> Result<()> {
+        if let Some(path) = &self.junit {
+            write_junit(path, name, cases)?;
+        }
+        if let Some(path) = &self.tap {
+            write_tap(path, cases)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a headless command and returns the process exit code: 0 on success, 1 when a
+/// connection, scenario step or script assertion failed and 2 on invalid usage.
+pub async fn run(command: Command, env: Option<String>) -> i32 {
+    // `environment` commands create the environment given with `--env`
+    let activated = match command {
+        Command::Environment { .. } => Ok(()),
+        _ => activate_environment(env.as_deref()),
+    };
+    if let Err(e) = activated {
+        eprintln!("Error: {}", e);
+        return 2;
+    }
+    let result = match command {
+        Command::Connect {
+            target,
+            send,
+            script,
+            duration,
+            record,
+        } => connect(&target, &send, script, duration, record).await,
+        Command::RunScenario {
+            file,
+            target,
+            connections,
+            report,
+        } => run_scenario(&file, target.as_deref(), connections, &report).await,
+        Command::Load {
+            target,
+            connections,
+            message,
+            interval,
+            count,
+            report,
+            record,
+        } => {
+            load(
+                &target,
+                connections,
+                &message,
+                interval,
+                count,
+                &report,
+                record,
+            )
+            .await
+        }
+        Command::Fuzz {
+            target,
+            iterations,
+            min_len,
+            max_len,
+            interval,
+        } => fuzz(&target, iterations, min_len, max_len, interval).await,
+        Command::Repl { target, record } => repl(&target, record).await,
+        Command::Replay {
+            file,
+            target,
+            speed,
+            fast,
+            ignore,
+        } => replay(&file, target.as_deref(), speed, fast, ignore).await,
+        Command::Golden { command } => golden(command).await,
+        Command::Environment { command } => environment(command, env),
+        Command::Workspace { command } => workspace(command),
+    };
+    match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            2
+        }
+    }
+}
+
+/// Resolves a URL, the id of a saved connection or a profile name (`workspace/profile`, or a
+/// profile of the active workspace) into a URL and the saved settings.
+fn resolve_target(target: &str, state: &AppState) -> Result<(String, Option<ConnectionProfile>)> {
+    if let Ok(id) = target.parse::<u8>() {
+        let window = state
+            .connections
+            .iter()
+            .find(|w| w.id == id)
+            .ok_or_else(|| anyhow!("No saved connection with id {}", id))?;
+        let profile = ConnectionProfile::from_window(id.to_string(), window);
+        return Ok((render_url(&profile.url), Some(profile)));
+    }
+    let url = render_url(target);
+    if is_valid_websocket_ip(&url) {
+        return Ok((url, None));
+    }
+    match state.find_profile(target) {
+        Some(profile) => Ok((render_url(&profile.url), Some(profile.clone()))),
+        None if target.contains("://") => bail!("Invalid WebSocket URL: {}", target),
+        None => bail!("No profile named {}", target),
+    }
+}
+
+async fn connect(
+    target: &str,
+    messages: &[String],
+    script: Option<PathBuf>,
+    duration: u64,
+    record: Option<PathBuf>,
+) -> Result<bool> {
+    let state = get_stored_app();
+    let (url, saved) = resolve_target(target, &state)?;
+    let script = match script {
+        Some(path) => Some(std::fs::read_to_string(path)?),
+        None => None,
+    };
+
+    let mut session = Session::start();
+    session.record(record).await?;
+    let id = 1;
+    if session.connect_all(&[id], &url).await.is_empty() {
+        return Ok(false);
+    }
+    session.configure(id, saved.as_ref(), script).await;
+
+    for (counter, template) in messages.iter().enumerate() {
+        session.send_payload(id, template, counter as u64).await;
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(duration);
+    let mut closed = false;
+    while let Some(event) = session.next_event(Some(deadline)).await {
+        if matches!(event, Message::Closed { .. }) {
+            closed = true;
+            break;
+        }
+    }
+    if !closed {
+        session.close(&[id]).await;
+    }
+    Ok(!closed && session.failures.is_empty())
+}
+
+async fn run_scenario(
+    file: &PathBuf,
+    target: Option<&str>,
+    connections: u8,
+    report: &ReportArgs,
+) -> Result<bool> {
+    let text = std::fs::read_to_string(file)?;
+    let scenario = parse_scenario(&file.to_string_lossy(), &text)?;
+    let state = get_stored_app();
+    let url = match target {
+        Some(target) => resolve_target(target, &state)?.0,
+        None => String::new(),
+    };
+
+    let mut session = Session::start();
+    for id in 1..=connections {
+        session
+            .send(Message::RunScenario {
+                id,
+                ip: url.clone(),
+                scenario: scenario.clone(),
+            })
+            .await;
+    }
+
+    let mut finished = 0;
+    let mut all_passed = true;
+    let mut results: HashMap<u8, Vec<StepResult>> = HashMap::new();
+    while finished < connections {
+        match session.next_event(None).await {
+            Some(Message::ScenarioStep { id, result }) => {
+                results.entry(id).or_default().push(result);
+            }
+            Some(Message::ScenarioDone { passed, .. }) => {
+                finished += 1;
+                all_passed &= passed;
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    // One case per executed step, loop iterations are numbered. Lines after the last executed
+    // one were never reached and are reported as skipped
+    let mut cases = Vec::new();
+    for id in 1..=connections {
+        let suite = format!("connection {}", id);
+        let mut runs: HashMap<usize, usize> = HashMap::new();
+        for result in results.remove(&id).unwrap_or_default() {
+            let run = runs.entry(result.line).or_default();
+            *run += 1;
+            let mut name = format!("line {}: {}", result.line, result.source);
+            if *run > 1 {
+                name = format!("{} (run {})", name, run);
+            }
+            cases.push(TestCase {
+                suite: suite.clone(),
+                name,
+                duration: result.duration,
+                failure: result.failure,
+                skipped: false,
+                output: result.frames,
+            });
+        }
+        let reached = runs.keys().max().copied().unwrap_or(0);
+        for (line, source) in scenario.lines() {
+            if line > reached {
+                cases.push(TestCase {
+                    suite: suite.clone(),
+                    name: format!("line {}: {}", line, source),
+                    duration: Duration::ZERO,
+                    failure: None,
+                    skipped: true,
+                    output: Vec::new(),
+                });
+            }
+        }
+    }
+    report.write(&scenario.name, &cases)?;
+    Ok(all_passed && session.failures.is_empty())
+}
+
+async fn load(
+    target: &str,
+    connections: u8,
+    message: &str,
+    interval: u64,
+    count: u32,
+    report: &ReportArgs,
+    record: Option<PathBuf>,
+) -> Result<bool> {
+    let state = get_stored_app();
+    let (url, saved) = resolve_target(target, &state)?;
+    let interval = Duration::from_millis(interval);
+
+    let mut session = Session::start();
+    session.record(record).await?;
+    let ids: Vec<u8> = (1..=connections).collect();
+    let mut alive = session.connect_all(&ids, &url).await;
+    let failed = ids.len() - alive.len();
+    let connected = alive.clone();
+    for id in &alive {
+        session.configure(*id, saved.as_ref(), None).await;
+    }
+
+    let started = Instant::now();
+    let mut sent = 0;
+    let mut dropped = HashMap::new();
+    for iteration in 0..count {
+        for id in &alive {
+            session.send_payload(*id, message, iteration as u64).await;
+            sent += 1;
+        }
+        let deadline = Instant::now() + interval;
+        while let Some(event) = session.next_event(Some(deadline)).await {
+            if let Message::Closed { id, code } = event {
+                alive.retain(|alive_id| *alive_id != id);
+                dropped.insert(id, (code, started.elapsed()));
+            }
+        }
+    }
+    session.close(&alive).await;
+
+    println!(
+        "{} connected, {} failed to connect, {} dropped; sent {} messages, received {} ({} bytes)",
+        ids.len() - failed,
+        failed,
+        dropped.len(),
+        sent,
+        session.received_frames,
+        session.received_bytes
+    );
+
+    let elapsed = started.elapsed();
+    let mut cases = Vec::new();
+    for id in &ids {
+        let suite = format!("connection {}", id);
+        let is_connected = connected.contains(id);
+        cases.push(TestCase {
+            suite: suite.clone(),
+            name: String::from("connects"),
+            duration: Duration::ZERO,
+            failure: (!is_connected).then(|| format!("Could not connect to {}", url)),
+            skipped: false,
+            output: Vec::new(),
+        });
+        cases.push(TestCase {
+            suite: suite.clone(),
+            name: String::from("stays connected"),
+            duration: dropped.get(id).map_or(elapsed, |(_, after)| *after),
+            failure: dropped.get(id).map(|(code, after)| match code {
+                Some(code) => format!("Closed with code {} after {} ms", code, after.as_millis()),
+                None => format!("Dropped after {} ms", after.as_millis()),
+            }),
+            skipped: !is_connected,
+            output: session.recent_frames(*id),
+        });
+        for (_, failure) in session.failures.iter().filter(|(failed, _)| failed == id) {
+            cases.push(TestCase {
+                suite: suite.clone(),
+                name: String::from("script assertion"),
+                duration: Duration::ZERO,
+                failure: Some(failure.clone()),
+                skipped: false,
+                output: Vec::new(),
+            });
+        }
+    }
+    report.write(&format!("load {}", url), &cases)?;
+    Ok(failed == 0 && dropped.is_empty() && session.failures.is_empty())
+}
+
+async fn fuzz(
+    target: &str,
+    iterations: u32,
+    min_len: usize,
+    max_len: usize,
+    interval: u64,
+) -> Result<bool> {
+    let state = get_stored_app();
+    let (url, _) = resolve_target(target, &state)?;
+    drop(state);
+
+    let mut session = Session::start();
+    let id = 1;
+    if session.connect_all(&[id], &url).await.is_empty() {
+        return Ok(false);
+    }
+
+    let mut findings = 0;
+    for _ in 0..iterations {
+        let payload = random_payload(min_len, max_len);
+        session.send_payload(id, &payload, 0).await;
+
+        let deadline = Instant::now() + Duration::from_millis(interval);
+        let mut close_code = None;
+        while let Some(event) = session.next_event(Some(deadline)).await {
+            if let Message::Closed { code, .. } = event {
+                close_code = Some(code);
+                break;
+            }
+        }
+        let Some(close_code) = close_code else {
+            continue;
+        };
+
+        if is_crash(close_code) {
+            session
+                .send(Message::Triage {
+                    id,
+                    ip: url.clone(),
+                    input: payload,
+                })
+                .await;
+            let deadline = Instant::now() + TRIAGE_TIMEOUT;
+            while let Some(event) = session.next_event(Some(deadline)).await {
+                if let Message::TriageDone { finding, .. } = event {
+                    if finding.reproducible {
+                        findings += 1;
+                    }
+                    let mut state = get_stored_app();
+                    state.findings.push(finding);
+                    store_app(&state)?;
+                    break;
+                }
+            }
+        }
+
+        if session.connect_all(&[id], &url).await.is_empty() {
+            bail!("Could not reconnect to {}", url);
+        }
+    }
+    session.close(&[id]).await;
+
+    println!("{} reproducible finding(s)", findings);
+    Ok(findings == 0)
+}
+
+async fn replay(
+    file: &Path,
+    target: Option<&str>,
+    speed: Option<f64>,
+    fast: bool,
+    ignore: Vec<String>,
+) -> Result<bool> {
+    let events = load_recording(file)?;
+    for pointer in &ignore {
+        validate_pointer(pointer)?;
+    }
+    let state = get_stored_app();
+    let target = match target {
+        Some(target) => Some(resolve_target(target, &state)?.0),
+        None => None,
+    };
+    let speed = match (speed, fast) {
+        (_, true) => ReplaySpeed::AsFastAsPossible,
+        (speed, false) => replay_speed(speed, ReplaySpeed::Original)?,
+    };
+    let report = run_replay(events, target, speed, ignore).await?;
+    Ok(report.passed())
+}
+
+fn replay_speed(factor: Option<f64>, default: ReplaySpeed) -> Result<ReplaySpeed> {
+    match factor {
+        Some(factor) if factor <= 0. => bail!("Speed must be positive"),
+        Some(factor) => Ok(ReplaySpeed::Scaled(factor)),
+        None => Ok(default),
+    }
+}
+
+/// Replays the recording through the network task and prints the differences.
+async fn run_replay(
+    events: Vec<RecordedEvent>,
+    target: Option<String>,
+    speed: ReplaySpeed,
+    ignore: Vec<String>,
+) -> Result<ReplayReport> {
+    let mut session = Session::start();
+    session
+        .send(Message::Replay {
+            golden: None,
+            events,
+            target,
+            speed,
+            ignore,
+        })
+        .await;
+    let report = loop {
+        match session.next_event(None).await {
+            Some(Message::ReplayDone { report, .. }) => break report,
+            Some(_) => {}
+            None => bail!("Network task stopped"),
+        }
+    };
+
+    for error in &report.errors {
+        println!("error: {}", error);
+    }
+    for diff in &report.diffs {
+        println!("#{} frame {}:", diff.conn, diff.index);
+        println!(
+            "  expected: {}",
+            diff.expected.as_deref().unwrap_or("<nothing>")
+        );
+        println!(
+            "  actual:   {}",
+            diff.actual.as_deref().unwrap_or("<nothing>")
+        );
+        for change in &diff.changes {
+            println!("    {}", change);
+        }
+    }
+    println!(
+        "{} connection(s), {} frames sent, {} compared, {} diff(s)",
+        report.connections.len(),
+        report.frames_sent,
+        report.frames_compared,
+        report.diffs.len()
+    );
+    Ok(report)
+}
+
+async fn golden(command: GoldenCommand) -> Result<bool> {
+    let mut state = get_stored_app();
+    match command {
+        GoldenCommand::Add { file, name, ignore } => {
+            load_recording(&file)?;
+            for pointer in &ignore {
+                validate_pointer(pointer)?;
+            }
+            let name = name.unwrap_or_else(|| {
+                file.file_stem()
+                    .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+            });
+            state.goldens.retain(|golden| golden.name != name);
+            state.goldens.push(GoldenRecording {
+                name,
+                path: file.to_string_lossy().into_owned(),
+                ignore,
+                last_run: None,
+                last_passed: None,
+                editing_ignore: String::new(),
+            });
+            store_app(&state)?;
+        }
+        GoldenCommand::Remove { name } => {
+            let count = state.goldens.len();
+            state.goldens.retain(|golden| golden.name != name);
+            if state.goldens.len() == count {
+                bail!("No golden recording named {}", name);
+            }
+            store_app(&state)?;
+        }
+        GoldenCommand::List => {
+            for golden in &state.goldens {
+                let outcome = match golden.last_passed {
+                    Some(true) => "passed",
+                    Some(false) => "failed",
+                    None => "never run",
+                };
+                println!(
+                    "{}  {}  ignore {:?}  {}",
+                    golden.name, golden.path, golden.ignore, outcome
+                );
+            }
+        }
+        GoldenCommand::Check {
+            name,
+            target,
+            speed,
+            report,
+        } => {
+            let target = match target {
+                Some(target) => Some(resolve_target(&target, &state)?.0),
+                None => None,
+            };
+            let speed = replay_speed(speed, ReplaySpeed::AsFastAsPossible)?;
+            let selected: Vec<usize> = (0..state.goldens.len())
+                .filter(|i| {
+                    name.as_ref()
+                        .is_none_or(|name| state.goldens[*i].name == *name)
+                })
+                .collect();
+            if selected.is_empty() {
+                bail!("No golden recording to check");
+            }
+
+            let mut cases = Vec::new();
+            let mut all_passed = true;
+            for index in selected {
+                let golden = state.goldens[index].clone();
+                println!("Checking {}", golden.name);
+                let started = Instant::now();
+                let events = load_recording(Path::new(&golden.path))?;
+                let result =
+                    run_replay(events, target.clone(), speed, golden.ignore.clone()).await?;
+                cases.extend(golden_cases(&golden.name, &result, started.elapsed()));
+                all_passed &= result.passed();
+                state.goldens[index].last_run = Some(Utc::now());
+                state.goldens[index].last_passed = Some(result.passed());
+            }
+            store_app(&state)?;
+            report.write("golden", &cases)?;
+            return Ok(all_passed);
+        }
+    }
+    Ok(true)
+}
+
+/// Makes the variables of the environment named `name`, or of the active one, available to
+/// templates.
+fn activate_environment(name: Option<&str>) -> Result<()> {
+    let state = get_stored_app();
+    match name {
+        Some(name) => {
+            let environment = state
+                .environments
+                .iter()
+                .find(|e| e.name == name)
+                .ok_or_else(|| anyhow!("No environment named {}", name))?;
+            environment::activate(Some(environment));
+        }
+        None => environment::activate(state.environment()),
+    }
+    Ok(())
+}
+
+fn environment(command: EnvironmentCommand, env: Option<String>) -> Result<bool> {
+    let mut state = get_stored_app();
+    match command {
+        EnvironmentCommand::List => {
+            for environment in &state.environments {
+                let marker = if environment.name == state.active_environment {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{} {}", marker, environment.name);
+                for (name, value) in &environment.variables {
+                    println!("    {} = {}", name, value);
+                }
+                for name in environment.secrets.keys() {
+                    println!("    {} = {} (secret)", name, MASK);
+                }
+            }
+            return Ok(true);
+        }
+        EnvironmentCommand::Use { name } => {
+            state.active_environment = name;
+            state.environment_mut();
+        }
+        EnvironmentCommand::Set {
+            name,
+            value,
+            secret,
+        } => {
+            let value = match value {
+                Some(value) => value,
+                None => {
+                    let mut line = String::new();
+                    std::io::stdin().read_line(&mut line)?;
+                    line.trim_end_matches(['\r', '\n']).to_string()
+                }
+            };
+            let environment = selected_environment(&mut state, env);
+            environment.variables.remove(&name);
+            environment.secrets.remove(&name);
+            if secret {
+                environment.secrets.insert(name, value);
+            } else {
+                environment.variables.insert(name, value);
+            }
+        }
+        EnvironmentCommand::Unset { name } => {
+            let environment = selected_environment(&mut state, env);
+            if environment.variables.remove(&name).is_none()
+                && environment.secrets.remove(&name).is_none()
+            {
+                bail!("No variable named {}", name);
+            }
+        }
+        EnvironmentCommand::Delete { name } => {
+            let count = state.environments.len();
+            state.environments.retain(|e| e.name != name);
+            if state.environments.len() == count {
+                bail!("No environment named {}", name);
+            }
+        }
+    }
+    store_app(&state)?;
+    Ok(true)
+}
+
+/// The environment named by `--env`, or the active one, created when it does not exist yet.
+fn selected_environment(state: &mut AppState, name: Option<String>) -> &mut Environment {
+    let Some(name) = name else {
+        return state.environment_mut();
+    };
+    let index = match state.environments.iter().position(|e| e.name == name) {
+        Some(index) => index,
+        None => {
+            state.environments.push(Environment {
+                name,
+                ..Default::default()
+            });
+            state.environments.len() - 1
+        }
+    };
+    &mut state.environments[index]
+}
+
+fn workspace(command: WorkspaceCommand) -> Result<bool> {
+    let mut state = get_stored_app();
+    match command {
+        WorkspaceCommand::List => {
+            for workspace in &state.workspaces {
+                let marker = if workspace.name == state.active_workspace {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{} {}", marker, workspace.name);
+                for profile in &workspace.profiles {
+                    println!("    {}  {}", profile.name, profile.url);
+                }
+            }
+            return Ok(true);
+        }
+        WorkspaceCommand::Use { name } => {
+            state.active_workspace = name;
+            state.workspace_mut();
+        }
+        WorkspaceCommand::Save { id, name } => {
+            let window = state
+                .connections
+                .iter()
+                .find(|w| w.id == id)
+                .ok_or_else(|| anyhow!("No saved connection with id {}", id))?;
+            let profile = ConnectionProfile::from_window(name, window);
+            let workspace = state.workspace_mut();
+            workspace.profiles.retain(|p| p.name != profile.name);
+            workspace.profiles.push(profile);
+        }
+        WorkspaceCommand::Remove { name } => match name.split_once('/') {
+            Some((workspace, profile)) => {
+                let workspace = state
+                    .workspaces
+                    .iter_mut()
+                    .find(|w| w.name == workspace)
+                    .ok_or_else(|| anyhow!("No workspace named {}", workspace))?;
+                let count = workspace.profiles.len();
+                workspace.profiles.retain(|p| p.name != profile);
+                if workspace.profiles.len() == count {
+                    bail!("No profile named {}", name);
+                }
+            }
+            None => {
+                let count = state.workspaces.len();
+                state.workspaces.retain(|w| w.name != name);
+                if state.workspaces.len() == count {
+                    bail!("No workspace named {}", name);
+                }
+            }
+        },
+        WorkspaceCommand::Export { name, file } => {
+            let name = name.unwrap_or_else(|| state.active_workspace.clone());
+            let workspace = state
+                .workspaces
+                .iter()
+                .find(|w| w.name == name)
+                .ok_or_else(|| anyhow!("No workspace named {}", name))?;
+            export_workspace(workspace, &file)?;
+            return Ok(true);
+        }
+        WorkspaceCommand::Import { file } => {
+            let workspace = import_workspace(&file)?;
+            let name = workspace.name.clone();
+            if add_workspace(&mut state, workspace) {
+                println!("Replaced workspace {}", name);
+            } else {
+                println!("Added workspace {}", name);
+            }
+        }
+    }
+    store_app(&state)?;
+    Ok(true)
+}
+
+/// One case per replayed connection of a golden recording, failing with its changed frames.
+fn golden_cases(name: &str, result: &ReplayReport, duration: Duration) -> Vec<TestCase> {
+    let mut cases: Vec<TestCase> = result
+        .connections
+        .iter()
+        .map(|conn| {
+            let diffs: Vec<String> = result
+                .diffs
+                .iter()
+                .filter(|diff| diff.conn == *conn)
+                .map(|diff| {
+                    let mut text = format!(
+                        "frame {}: expected {} but got {}",
+                        diff.index,
+                        diff.expected.as_deref().unwrap_or("<nothing>"),
+                        diff.actual.as_deref().unwrap_or("<nothing>")
+                    );
+                    for change in &diff.changes {
+                        text.push_str(&format!("\n  {}", change));
+                    }
+                    text
+                })
+                .collect();
+            TestCase {
+                suite: name.to_string(),
+                name: format!("connection {}", conn),
+                duration,
+                failure: (!diffs.is_empty())
+                    .then(|| format!("{} response(s) changed", diffs.len())),
+                skipped: false,
+                output: diffs,
+            }
+        })
+        .collect();
+    for error in &result.errors {
+        cases.push(TestCase {
+            suite: name.to_string(),
+            name: String::from("replay"),
+            duration,
+            failure: Some(error.clone()),
+            skipped: false,
+            output: Vec::new(),
+        });
+    }
+    cases
+}
+
+const REPL_HELP: &str = "\
+Lines are sent as text frames, templates are expanded. Commands:
+  /ping [text]            send a ping frame
+  /close [code] [reason]  close the connection
+  /binary <hex>           send a binary frame
+  /snippet <name>         send a snippet of the library, [folder/]name
+  /headers                show the handshake response headers
+  /quit                   close and exit
+  //text                  send a line starting with '/'";
+
+async fn repl(target: &str, record: Option<PathBuf>) -> Result<bool> {
+    let state = get_stored_app();
+    let (url, saved) = resolve_target(target, &state)?;
+
+    let mut session = Session::start();
+    session.record(record).await?;
+    let id = 1;
+    if session.connect_all(&[id], &url).await.is_empty() {
+        return Ok(false);
+    }
+    session.configure(id, saved.as_ref(), None).await;
+    println!("{}", REPL_HELP);
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut counter = 0;
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    session.close(&[id]).await;
+                    return Ok(true);
+                };
+                let Some(command) = line.strip_prefix('/').filter(|c| !c.starts_with('/')) else {
+                    let text = line.strip_prefix('/').unwrap_or(&line);
+                    session.send_payload(id, text, counter).await;
+                    counter += 1;
+                    continue;
+                };
+                let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+                match name {
+                    "ping" => {
+                        println!("[{}] #{} > [ping] {}", timestamp(), id, args);
+                        session
+                            .send(Message::Ping {
+                                id,
+                                payload: args.as_bytes().to_vec(),
+                            })
+                            .await
+                    }
+                    "close" => {
+                        let (code, reason) = args.split_once(' ').unwrap_or((args, ""));
+                        let code = match code {
+                            "" => None,
+                            code => match code.parse() {
+                                Ok(code) => Some(code),
+                                Err(_) => {
+                                    eprintln!("Invalid close code: {}", code);
+                                    continue;
+                                }
+                            },
+                        };
+                        println!("[{}] #{} > [close] {}", timestamp(), id, args);
+                        session
+                            .send(Message::Close {
+                                id,
+                                code,
+                                reason: reason.to_string(),
+                            })
+                            .await;
+                    }
+                    "binary" => match parse_hex(args) {
+                        Some(data) => {
+                            println!("[{}] #{} > [binary] {}", timestamp(), id, args.trim());
+                            session.send(Message::Binary { id, data }).await;
+                        }
+                        None => eprintln!("Invalid hex: {}", args),
+                    },
+                    "snippet" => {
+                        let (folder, name) = args.trim().rsplit_once('/').unwrap_or(("", args.trim()));
+                        let Some(snippet) = state
+                            .snippets
+                            .iter()
+                            .find(|s| s.name == name && (folder.is_empty() || s.folder == folder))
+                        else {
+                            eprintln!("No snippet named {}", args.trim());
+                            continue;
+                        };
+                        match snippet.kind {
+                            SnippetKind::Text => {
+                                session.send_payload(id, &snippet.payload, counter).await;
+                                counter += 1;
+                            }
+                            SnippetKind::Binary => {
+                                let data = parse_hex(&snippet.payload).unwrap_or_default();
+                                println!("[{}] #{} > [binary] {}", timestamp(), id, snippet.payload.trim());
+                                session.send(Message::Binary { id, data }).await;
+                            }
+                        }
+                    }
+                    "headers" => match &session.handshake {
+                        Some((status, headers)) => {
+                            println!("HTTP {}", status);
+                            for (name, value) in headers {
+                                println!("{}: {}", name, value);
+                            }
+                        }
+                        None => println!("No handshake received"),
+                    },
+                    "quit" => {
+                        session.close(&[id]).await;
+                        return Ok(true);
+                    }
+                    _ => println!("{}", REPL_HELP),
+                }
+            }
+            event = session.next_event(None) => {
+                if matches!(event, None | Some(Message::Closed { .. })) {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+}
+
+/// Drives `network_processor` without the UI, printing every event it reports.
+struct Session {
+    to_network: Sender<Message>,
+    from_network: Receiver<Message>,
+    rules: HashMap<u8, Vec<ExtractionRule>>,
+    variables: HashMap<u8, HashMap<String, String>>,
+    handshake: Option<(u16, Vec<(String, String)>)>,
+    failures: Vec<(u8, String)>,
+    frames: HashMap<u8, VecDeque<String>>,
+    received_frames: usize,
+    received_bytes: usize,
+}
+
+impl Session {
+    fn start() -> Self {
+        let (to_network, mut ui2nrx) = mpsc::channel::<Message>(12);
+        let (mut n2uitx, from_network) = mpsc::channel::<Message>(200);
+        tokio::spawn(async move {
+            network_processor(&mut ui2nrx, &mut n2uitx).await;
+        });
+        Self {
+            to_network,
+            from_network,
+            rules: HashMap::new(),
+            variables: HashMap::new(),
+            handshake: None,
+            failures: Vec::new(),
+            frames: HashMap::new(),
+            received_frames: 0,
+            received_bytes: 0,
+        }
+    }
+
+    async fn send(&self, message: Message) {
+        let _ = self.to_network.send(message).await;
+    }
+
+    /// Records every connection of the session to `path`, if given.
+    async fn record(&self, path: Option<PathBuf>) -> Result<()> {
+        if let Some(path) = path {
+            let recorder = Recorder::create(&path)?;
+            self.send(Message::StartRecording { recorder }).await;
+        }
+        Ok(())
+    }
+
+    /// Opens a connection per id and returns the ids that connected.
+    async fn connect_all(&mut self, ids: &[u8], url: &str) -> Vec<u8> {
+        for id in ids {
+            self.send(Message::NewClient {
+                id: *id,
+                ip: url.to_string(),
+            })
+            .await;
+        }
+        let mut connected = Vec::new();
+        let mut pending = ids.len();
+        let deadline = Instant::now() + CONNECT_TIMEOUT * ids.len() as u32;
+        while pending > 0 {
+            match self.next_event(Some(deadline)).await {
+                Some(Message::NewClient { id, .. }) if ids.contains(&id) => {
+                    connected.push(id);
+                    pending -= 1;
+                }
+                Some(Message::Closed { id, .. }) if ids.contains(&id) => {
+                    connected.retain(|connected_id| *connected_id != id);
+                    pending = pending.saturating_sub(1);
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+        connected
+    }
+
+    /// Applies the script, auto-responders and extraction rules of a saved connection.
+    async fn configure(
+        &mut self,
+        id: u8,
+        saved: Option<&ConnectionProfile>,
+        script: Option<String>,
+    ) {
+        let script = script.or_else(|| {
+            saved
+                .map(|profile| profile.script.clone())
+                .filter(|s| !s.is_empty())
+        });
+        if let Some(source) = script {
+            self.send(Message::SetScript {
+                id,
+                source: Some(source),
+            })
+            .await;
+        }
+        if let Some(profile) = saved {
+            if !profile.auto_responders.is_empty() {
+                self.send(Message::SetAutoResponders {
+                    id,
+                    responders: profile.auto_responders.clone(),
+                })
+                .await;
+            }
+            self.rules.insert(id, profile.extraction_rules.clone());
+        }
+    }
+
+    /// Gives the connection's task the variables its auto-responders render responses with.
+    async fn send_variables(&self, id: u8) {
+        let variables = self.variables.get(&id).cloned().unwrap_or_default();
+        self.send(Message::SetVariables { id, variables }).await;
+    }
+
+    async fn send_payload(&mut self, id: u8, template: &str, counter: u64) {
+        let variables = self.variables.entry(id).or_default();
+        let payload = render_template(
+            template,
+            &TemplateContext {
+                conn_id: id,
+                counter,
+                variables,
+            },
+        );
+        print_event(&Message::Sent {
+            id,
+            payload: payload.clone(),
+        });
+        self.send(Message::Payload {
+            id,
+            num_bytes: payload.len(),
+            payload,
+        })
+        .await;
+    }
+
+    async fn close(&mut self, ids: &[u8]) {
+        for id in ids {
+            self.send(Message::Close {
+                id: *id,
+                code: None,
+                reason: String::new(),
+            })
+            .await;
+        }
+        let deadline = Instant::now() + GRACE_PERIOD;
+        while self.next_event(Some(deadline)).await.is_some() {}
+    }
+
+    /// Waits for the next event from the network, printing it and keeping track of failures,
+    /// traffic and extracted variables. Returns `None` once `deadline` passes.
+    async fn next_event(&mut self, deadline: Option<Instant>) -> Option<Message> {
+        let message = match deadline {
+            Some(deadline) => timeout_at(deadline, self.from_network.recv())
+                .await
+                .ok()
+                .flatten()?,
+            None => self.from_network.recv().await?,
+        };
+        print_event(&message);
+
+        match &message {
+            Message::Payload {
+                id,
+                payload,
+                num_bytes,
+            } => {
+                self.received_frames += 1;
+                self.received_bytes += num_bytes;
+                let frames = self.frames.entry(*id).or_default();
+                if frames.len() == REPORTED_FRAMES {
+                    frames.pop_front();
+                }
+                frames.push_back(mask(payload));
+                if let Some(rules) = self.rules.get(id) {
+                    let extracted = extract(rules, payload);
+                    if !extracted.is_empty() {
+                        self.variables.entry(*id).or_default().extend(extracted);
+                        self.send_variables(*id).await;
+                    }
+                }
+            }
+            Message::Binary { id, data } => {
+                self.received_frames += 1;
+                self.received_bytes += data.len();
+                let frames = self.frames.entry(*id).or_default();
+                if frames.len() == REPORTED_FRAMES {
+                    frames.pop_front();
+                }
+                frames.push_back(format!("[binary] {}", masked_hex(data)));
+            }
+            Message::SetVariable { id, name, value } => {
+                self.variables
+                    .entry(*id)
+                    .or_default()
+                    .insert(name.clone(), value.clone());
+                self.send_variables(*id).await;
+            }
+            Message::Handshake {
+                status, headers, ..
+            } => self.handshake = Some((*status, headers.clone())),
+            Message::ScriptLog {
+                id,
+                line,
+                is_error: true,
+            } => self.failures.push((*id, line.clone())),
+            Message::ScenarioStep { id, result } => {
+                if let Some(failure) = &result.failure {
+                    self.failures.push((*id, failure.clone()));
+                }
+            }
+            _ => {}
+        }
+        Some(message)
+    }
+
+    fn recent_frames(&self, id: u8) -> Vec<String> {
+        self.frames
+            .get(&id)
+            .map(|frames| frames.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+fn print_event(message: &Message) {
+    let line = match message {
+        Message::NewClient { id, ip } => format!("#{} connected to {}", id, ip),
+        Message::Payload { id, payload, .. } => format!("#{} < {}", id, payload),
+        Message::Binary { id, data } => format!("#{} < [binary] {}", id, masked_hex(data)),
+        Message::Sent { id, payload } => format!("#{} > {}", id, payload),
+        Message::Closed { id, code } => format!("#{} closed (code: {:?})", id, code),
+        Message::Handshake { id, status, .. } => format!("#{} handshake: HTTP {}", id, status),
+        Message::Pong { id, rtt } => match rtt {
+            Some(rtt) => format!("#{} < pong ({} ms)", id, rtt.as_millis()),
+            None => format!("#{} < pong", id),
+        },
+        Message::ScriptLog { id, line, is_error } => {
+            let prefix = if *is_error {
+                "[script error]"
+            } else {
+                "[script]"
+            };
+            format!("#{} {} {}", id, prefix, line)
+        }
+        Message::SetVariable { id, name, value } => format!("#{} {} = {}", id, name, value),
+        Message::ScenarioStep { id, result } => match &result.failure {
+            Some(failure) => format!("#{} FAIL {}", id, failure),
+            None => format!(
+                "#{} ok   line {}: {} ({} ms)",
+                id,
+                result.line,
+                result.source,
+                result.duration.as_millis()
+            ),
+        },
+        Message::ScenarioDone { id, passed } => {
+            let outcome = if *passed { "passed" } else { "failed" };
+            format!("#{} scenario {}", id, outcome)
+        }
+        Message::TriageDone { id, finding } => format!(
+            "#{} finding: reproducible={}, close code {:?}, minimised from {} to {} chars: {}",
+            id,
+            finding.reproducible,
+            finding.close_code,
+            finding.input.chars().count(),
+            finding.minimised_input.chars().count(),
+            finding.minimised_input
+        ),
+        _ => return,
+    };
+    println!("[{}] {}", timestamp(), mask(&line));
+}
+
+fn timestamp() -> String {
+    Local::now().format("%H:%M:%S%.3f").to_string()
+}
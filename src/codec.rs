@@ -0,0 +1,577 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use protobuf::descriptor::field_descriptor_proto::Type;
+use protobuf::descriptor::FileDescriptorSet;
+use protobuf::reflect::{
+    FileDescriptor, MessageDescriptor, ReflectFieldRef, ReflectValueRef, Syntax,
+};
+use protobuf::{CodedOutputStream, Message as _, MessageDyn};
+use serde_json::{Map, Number, Value};
+
+use crate::structs::{CodecKind, CodecSettings};
+use crate::utils::{parse_hex, to_hex};
+
+/// A binary payload format. Binary frames are decoded to JSON to be shown like text messages,
+/// and JSON typed in the composer is encoded to a binary frame.
+pub trait Codec: Send + Sync {
+    /// Short name shown next to decoded messages
+    fn name(&self) -> &'static str;
+    fn decode(&self, data: &[u8]) -> Result<Value>;
+    fn encode(&self, value: &Value) -> Result<Vec<u8>>;
+}
+
+/// The codec described by `settings`, `None` when binary frames are shown as they are.
+pub fn load_codec(settings: &CodecSettings) -> Result<Option<Box<dyn Codec>>> {
+    Ok(match settings.kind {
+        CodecKind::None => None,
+        CodecKind::MessagePack => Some(Box::new(MessagePack)),
+        CodecKind::Cbor => Some(Box::new(Cbor)),
+        CodecKind::Protobuf => Some(Box::new(Protobuf::load(
+            Path::new(settings.proto_path.trim()),
+            settings.message_type.trim(),
+        )?)),
+    })
+}
+
+/// MessagePack, with binary values shown as hex strings and extension values as
+/// `{"ext": type, "data": hex}`.
+pub struct MessagePack;
+
+impl Codec for MessagePack {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Value> {
+        let mut reader = data;
+        let value = rmpv::decode::read_value(&mut reader)?;
+        if !reader.is_empty() {
+            bail!("{} bytes left after the MessagePack value", reader.len());
+        }
+        Ok(msgpack_to_json(value))
+    }
+
+    fn encode(&self, value: &Value) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        rmpv::encode::write_value(&mut data, &json_to_msgpack(value))?;
+        Ok(data)
+    }
+}
+
+fn msgpack_to_json(value: rmpv::Value) -> Value {
+    match value {
+        rmpv::Value::Nil => Value::Null,
+        rmpv::Value::Boolean(b) => Value::Bool(b),
+        rmpv::Value::Integer(i) => match (i.as_u64(), i.as_i64()) {
+            (Some(u), _) => Value::from(u),
+            (_, Some(i)) => Value::from(i),
+            _ => Value::Null,
+        },
+        rmpv::Value::F32(f) => float(f64::from(f)),
+        rmpv::Value::F64(f) => float(f),
+        rmpv::Value::String(s) => match s.into_str() {
+            Some(s) => Value::String(s),
+            None => Value::Null,
+        },
+        rmpv::Value::Binary(data) => Value::String(to_hex(&data)),
+        rmpv::Value::Array(items) => Value::Array(items.into_iter().map(msgpack_to_json).collect()),
+        rmpv::Value::Map(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        rmpv::Value::String(s) if s.is_str() => s.into_str().unwrap_or_default(),
+                        other => other.to_string(),
+                    };
+                    (key, msgpack_to_json(value))
+                })
+                .collect(),
+        ),
+        rmpv::Value::Ext(kind, data) => {
+            let mut ext = Map::new();
+            ext.insert(String::from("ext"), Value::from(kind));
+            ext.insert(String::from("data"), Value::String(to_hex(&data)));
+            Value::Object(ext)
+        }
+    }
+}
+
+fn json_to_msgpack(value: &Value) -> rmpv::Value {
+    match value {
+        Value::Null => rmpv::Value::Nil,
+        Value::Bool(b) => rmpv::Value::Boolean(*b),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(u), _) => rmpv::Value::from(u),
+            (_, Some(i)) => rmpv::Value::from(i),
+            _ => rmpv::Value::F64(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => rmpv::Value::from(s.as_str()),
+        Value::Array(items) => rmpv::Value::Array(items.iter().map(json_to_msgpack).collect()),
+        Value::Object(map) => {
+            // The way extension values are decoded
+            if let (Some(kind), Some(data), 2) = (
+                map.get("ext").and_then(Value::as_i64),
+                map.get("data").and_then(Value::as_str).and_then(parse_hex),
+                map.len(),
+            ) {
+                if let Ok(kind) = i8::try_from(kind) {
+                    return rmpv::Value::Ext(kind, data);
+                }
+            }
+            rmpv::Value::Map(
+                map.iter()
+                    .map(|(key, value)| (rmpv::Value::from(key.as_str()), json_to_msgpack(value)))
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// CBOR, with byte strings shown as hex strings. Tags are dropped.
+pub struct Cbor;
+
+impl Codec for Cbor {
+    fn name(&self) -> &'static str {
+        "cbor"
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Value> {
+        let mut reader = data;
+        let value: ciborium::Value = ciborium::de::from_reader(&mut reader)?;
+        if !reader.is_empty() {
+            bail!("{} bytes left after the CBOR value", reader.len());
+        }
+        Ok(cbor_to_json(value))
+    }
+
+    fn encode(&self, value: &Value) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        ciborium::ser::into_writer(value, &mut data)?;
+        Ok(data)
+    }
+}
+
+fn cbor_to_json(value: ciborium::Value) -> Value {
+    match value {
+        ciborium::Value::Null => Value::Null,
+        ciborium::Value::Bool(b) => Value::Bool(b),
+        ciborium::Value::Integer(i) => {
+            let i = i128::from(i);
+            match (u64::try_from(i), i64::try_from(i)) {
+                (Ok(u), _) => Value::from(u),
+                (_, Ok(i)) => Value::from(i),
+                _ => Value::String(i.to_string()),
+            }
+        }
+        ciborium::Value::Float(f) => float(f),
+        ciborium::Value::Text(s) => Value::String(s),
+        ciborium::Value::Bytes(data) => Value::String(to_hex(&data)),
+        ciborium::Value::Array(items) => {
+            Value::Array(items.into_iter().map(cbor_to_json).collect())
+        }
+        ciborium::Value::Map(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = match cbor_to_json(key) {
+                        Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    (key, cbor_to_json(value))
+                })
+                .collect(),
+        ),
+        ciborium::Value::Tag(_, value) => cbor_to_json(*value),
+        _ => Value::Null,
+    }
+}
+
+/// JSON has no NaN or infinity, those become null.
+fn float(f: f64) -> Value {
+    Number::from_f64(f).map_or(Value::Null, Value::Number)
+}
+
+/// Protobuf messages of one type, using the proto3 JSON mapping of `protobuf-json-mapping`.
+pub struct Protobuf {
+    message: MessageDescriptor,
+}
+
+impl Protobuf {
+    /// Loads the message type `name`, e.g. `chat.Envelope`, from a `.proto` file or from a
+    /// descriptor set written by `protoc --descriptor_set_out`. Imports of a `.proto` file are
+    /// looked up next to it.
+    pub fn load(path: &Path, name: &str) -> Result<Self> {
+        if path.as_os_str().is_empty() {
+            bail!("Choose a .proto file or descriptor set");
+        }
+        // The file and everything it imports, including the well-known types
+        let files = if path.extension().is_some_and(|e| e == "proto") {
+            let include = path.parent().unwrap_or(Path::new("."));
+            protobuf_parse::Parser::new()
+                .pure()
+                .include(include)
+                .input(path)
+                .parse_and_typecheck()?
+                .file_descriptors
+        } else {
+            FileDescriptorSet::parse_from_bytes(&fs::read(path)?)?.file
+        };
+        // The JSON mapping only recognises the generated well-known types, so they are used
+        // instead of the parsed copies
+        let well_known = well_known_files();
+        let files: Vec<_> = files
+            .into_iter()
+            .filter(|file| well_known.iter().all(|wk| wk.proto().name() != file.name()))
+            .collect();
+        let files = FileDescriptor::new_dynamic_fds(files, &well_known)?;
+        let full_name = format!(".{}", name.trim_start_matches('.'));
+        let message = files
+            .iter()
+            .find_map(|file| file.message_by_full_name(&full_name))
+            .ok_or_else(|| {
+                let mut names = Vec::new();
+                for file in &files {
+                    file.messages()
+                        .for_each(|message| message_names(message, &mut names));
+                }
+                anyhow!(
+                    "Unknown message type {:?}, {} defines {}",
+                    name,
+                    path.display(),
+                    names.join(", ")
+                )
+            })?;
+        Ok(Self { message })
+    }
+}
+
+impl Codec for Protobuf {
+    fn name(&self) -> &'static str {
+        "protobuf"
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Value> {
+        let message = self.message.parse_from_bytes(data)?;
+        Ok(serde_json::from_str(
+            &protobuf_json_mapping::print_to_string(&*message)?,
+        )?)
+    }
+
+    fn encode(&self, value: &Value) -> Result<Vec<u8>> {
+        let message = protobuf_json_mapping::parse_dyn_from_str(&self.message, &value.to_string())?;
+        let mut data = message.write_to_bytes_dyn()?;
+        data.extend(zero_fields_with_presence(&*message)?);
+        Ok(data)
+    }
+}
+
+fn well_known_files() -> Vec<FileDescriptor> {
+    use protobuf::well_known_types::*;
+    vec![
+        any::file_descriptor().clone(),
+        api::file_descriptor().clone(),
+        duration::file_descriptor().clone(),
+        empty::file_descriptor().clone(),
+        field_mask::file_descriptor().clone(),
+        source_context::file_descriptor().clone(),
+        struct_::file_descriptor().clone(),
+        timestamp::file_descriptor().clone(),
+        type_::file_descriptor().clone(),
+        wrappers::file_descriptor().clone(),
+    ]
+}
+
+fn message_names(message: MessageDescriptor, names: &mut Vec<String>) {
+    if message.is_map_entry() {
+        return;
+    }
+    names.push(message.full_name().to_string());
+    message
+        .nested_messages()
+        .for_each(|nested| message_names(nested, names));
+}
+
+/// The encoder of dynamic messages leaves out every proto3 scalar holding its zero value, also
+/// those of `optional` fields and oneof members, which are set nonetheless. This is the wire
+/// form of those fields, of `message` and of its singular message fields, which decoders merge
+/// into the message encoded before. Zeros in messages inside repeated fields and maps are still
+/// left out.
+fn zero_fields_with_presence(message: &dyn MessageDyn) -> Result<Vec<u8>> {
+    let descriptor = message.descriptor_dyn();
+    let file = descriptor.file_descriptor();
+    let mut data = Vec::new();
+    // Generated messages, i.e. the well-known types, are encoded right
+    if well_known_files().contains(file) {
+        return Ok(data);
+    }
+    let proto3 = file.syntax() == Syntax::Proto3;
+    let mut output = CodedOutputStream::vec(&mut data);
+    for field in descriptor.fields() {
+        let ReflectFieldRef::Optional(value) = field.get_reflect(message) else {
+            continue;
+        };
+        let number = field.number() as u32;
+        match value.value() {
+            Some(ReflectValueRef::Message(nested)) => {
+                let nested = zero_fields_with_presence(&*nested)?;
+                if !nested.is_empty() {
+                    output.write_bytes(number, &nested)?;
+                }
+            }
+            Some(value)
+                if proto3
+                    && field.containing_oneof_including_synthetic().is_some()
+                    && value == field.singular_default_value() =>
+            {
+                match (value, field.proto().type_()) {
+                    (ReflectValueRef::F32(f), _) => output.write_float(number, f)?,
+                    (ReflectValueRef::F64(f), _) => output.write_double(number, f)?,
+                    (ReflectValueRef::String(_) | ReflectValueRef::Bytes(_), _) => {
+                        output.write_bytes(number, &[])?
+                    }
+                    (_, Type::TYPE_FIXED32 | Type::TYPE_SFIXED32) => {
+                        output.write_fixed32(number, 0)?
+                    }
+                    (_, Type::TYPE_FIXED64 | Type::TYPE_SFIXED64) => {
+                        output.write_fixed64(number, 0)?
+                    }
+                    // Every other zero is the varint 0
+                    _ => output.write_uint64(number, 0)?,
+                }
+            }
+            _ => {}
+        }
+    }
+    output.flush()?;
+    drop(output);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn sample() -> Value {
+        json!({
+            "type": "move",
+            "seq": 18446744073709551615u64,
+            "delta": -42,
+            "speed": 1.5,
+            "tags": ["a", "ß", ""],
+            "player": {"id": 7, "ready": true, "team": null},
+            "empty": {}
+        })
+    }
+
+    #[test]
+    fn msgpack_round_trip() {
+        let codec = MessagePack;
+        let data = codec.encode(&sample()).unwrap();
+        assert_eq!(codec.decode(&data).unwrap(), sample());
+    }
+
+    #[test]
+    fn msgpack_binary_and_ext_values() {
+        let value = rmpv::Value::Map(vec![
+            (
+                rmpv::Value::from("blob"),
+                rmpv::Value::Binary(vec![0xde, 0xad]),
+            ),
+            (rmpv::Value::from("ext"), rmpv::Value::Ext(5, vec![1, 2])),
+            (rmpv::Value::from(1), rmpv::Value::F32(0.5)),
+        ]);
+        let mut data = Vec::new();
+        rmpv::encode::write_value(&mut data, &value).unwrap();
+        let decoded = MessagePack.decode(&data).unwrap();
+        assert_eq!(
+            decoded,
+            json!({"blob": "de ad", "ext": {"ext": 5, "data": "01 02"}, "1": 0.5})
+        );
+
+        // Extension values are encoded back from their JSON form
+        let ext = json!({"ext": 5, "data": "01 02"});
+        let data = MessagePack.encode(&ext).unwrap();
+        let mut reader = data.as_slice();
+        assert_eq!(
+            rmpv::decode::read_value(&mut reader).unwrap(),
+            rmpv::Value::Ext(5, vec![1, 2])
+        );
+    }
+
+    #[test]
+    fn msgpack_rejects_trailing_bytes() {
+        let mut data = MessagePack.encode(&json!(1)).unwrap();
+        data.push(0xc0);
+        assert!(MessagePack.decode(&data).is_err());
+    }
+
+    #[test]
+    fn cbor_round_trip() {
+        let codec = Cbor;
+        let data = codec.encode(&sample()).unwrap();
+        assert_eq!(codec.decode(&data).unwrap(), sample());
+    }
+
+    #[test]
+    fn cbor_bytes_tags_and_keys() {
+        let value = ciborium::Value::Map(vec![
+            (
+                ciborium::Value::Text(String::from("blob")),
+                ciborium::Value::Bytes(vec![0xbe, 0xef]),
+            ),
+            (
+                ciborium::Value::Integer(2.into()),
+                ciborium::Value::Tag(1, Box::new(ciborium::Value::Integer(1700000000.into()))),
+            ),
+            (
+                ciborium::Value::Text(String::from("nan")),
+                ciborium::Value::Float(f64::NAN),
+            ),
+        ]);
+        let mut data = Vec::new();
+        ciborium::ser::into_writer(&value, &mut data).unwrap();
+        assert_eq!(
+            Cbor.decode(&data).unwrap(),
+            json!({"blob": "be ef", "2": 1700000000, "nan": null})
+        );
+    }
+
+    #[test]
+    fn cbor_rejects_trailing_bytes() {
+        let mut data = Cbor.encode(&json!("x")).unwrap();
+        data.push(0xf6);
+        assert!(Cbor.decode(&data).is_err());
+    }
+
+    fn envelope() -> Protobuf {
+        let dir = std::env::temp_dir().join(format!("codec-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("chat.proto");
+        fs::write(
+            &path,
+            r#"
+            syntax = "proto3";
+            package chat;
+            import "google/protobuf/duration.proto";
+            import "google/protobuf/struct.proto";
+            import "google/protobuf/timestamp.proto";
+            import "google/protobuf/wrappers.proto";
+
+            enum Kind { KIND_UNSPECIFIED = 0; KIND_TEXT = 1; }
+
+            message Envelope {
+                message Author {
+                    string display_name = 1;
+                    optional bool verified = 2;
+                }
+                int64 seq = 1;
+                Kind kind = 2;
+                string body = 3;
+                bytes blob = 4;
+                repeated double scores = 5;
+                map<int32, string> labels = 6;
+                Author author = 7;
+                google.protobuf.Timestamp sent_at = 8;
+                google.protobuf.Duration ttl = 9;
+                google.protobuf.Struct extra = 10;
+                google.protobuf.Int32Value retries = 11;
+                optional uint32 flags = 12;
+                oneof payload {
+                    uint32 code = 13;
+                    string note = 14;
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        let codec = Protobuf::load(&path, "chat.Envelope");
+        fs::remove_dir_all(&dir).unwrap();
+        codec.unwrap()
+    }
+
+    #[test]
+    fn protobuf_round_trip() {
+        let codec = envelope();
+        let value = json!({
+            "seq": "-9007199254740993",
+            "kind": "KIND_TEXT",
+            "body": "hi",
+            "blob": "3q2+7w==",
+            "scores": [1.5, "NaN", "-Infinity"],
+            "labels": {"7": "seven"},
+            "author": {"displayName": "ann"},
+            "sentAt": "2024-03-01T12:00:00.250000000Z",
+            "ttl": "-1.500000000s",
+            "extra": {"a": [1.0, null, true, {"b": "c"}], "zero": 0.0, "no": false},
+            "retries": 0,
+            "flags": 0
+        });
+        let data = codec.encode(&value).unwrap();
+        assert_eq!(codec.decode(&data).unwrap(), value);
+    }
+
+    #[test]
+    fn protobuf_json_input_forms() {
+        let codec = envelope();
+        // Proto field names, numbers for 64-bit integers and enums
+        let data = codec
+            .encode(&json!({
+                "seq": 5,
+                "kind": 1,
+                "author": {"display_name": "bo"},
+                "ttl": "2s"
+            }))
+            .unwrap();
+        assert_eq!(
+            codec.decode(&data).unwrap(),
+            json!({
+                "seq": "5",
+                "kind": "KIND_TEXT",
+                "author": {"displayName": "bo"},
+                "ttl": "2.000000000s"
+            })
+        );
+        // Defaults without presence are left out
+        assert_eq!(codec.decode(&[]).unwrap(), json!({}));
+        assert!(codec.encode(&json!({"nope": 1})).is_err());
+        assert!(codec.encode(&json!({"kind": "KIND_NOPE"})).is_err());
+    }
+
+    #[test]
+    fn protobuf_keeps_zero_values_that_are_set() {
+        let codec = envelope();
+        let value = json!({"flags": 0, "code": 0, "author": {"verified": false}});
+        // The encoder of dynamic messages drops them
+        let message =
+            protobuf_json_mapping::parse_dyn_from_str(&codec.message, &value.to_string()).unwrap();
+        let data = message.write_to_bytes_dyn().unwrap();
+        assert_eq!(codec.decode(&data).unwrap(), json!({"author": {}}));
+
+        let data = codec.encode(&value).unwrap();
+        assert_eq!(codec.decode(&data).unwrap(), value);
+    }
+
+    #[test]
+    fn protobuf_unknown_type_lists_the_types() {
+        let dir = std::env::temp_dir().join(format!("codec-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.proto");
+        fs::write(
+            &path,
+            "syntax = \"proto3\"; package a; message B { message C {} }",
+        )
+        .unwrap();
+        let error = Protobuf::load(&path, "a.D").err().unwrap().to_string();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(error.ends_with("defines a.B, a.B.C"), "{}", error);
+    }
+
+    #[test]
+    fn no_codec_by_default() {
+        assert!(load_codec(&CodecSettings::default()).unwrap().is_none());
+    }
+}
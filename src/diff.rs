@@ -0,0 +1,278 @@
+use serde_json::Value;
+
+use crate::utils::json_path_key;
+
+// Above this many line pairs the line diff gives up on aligning the changed middle part
+const MAX_LINE_PAIRS: usize = 4_000_000;
+
+/// Differences between two messages, by JSON value when both are JSON and by line otherwise.
+pub enum Diff {
+    Json(Vec<JsonChange>),
+    Lines(Vec<DiffLine>),
+}
+
+/// A value that differs between two JSON documents, at its JSONPath.
+pub struct JsonChange {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+pub enum ChangeKind {
+    Added(Value),
+    Removed(Value),
+    Changed(Value, Value),
+}
+
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+pub fn diff(old: &str, new: &str) -> Diff {
+    match (
+        serde_json::from_str::<Value>(old),
+        serde_json::from_str::<Value>(new),
+    ) {
+        (Ok(old), Ok(new)) => {
+            let mut changes = Vec::new();
+            json_diff(&old, &new, "$", &mut changes);
+            Diff::Json(changes)
+        }
+        _ => Diff::Lines(line_diff(old, new)),
+    }
+}
+
+/// Adds the changes from `old` to `new` to `changes`. Objects are compared by key and arrays
+/// by index, any other difference replaces the value.
+pub fn json_diff(old: &Value, new: &Value, path: &str, changes: &mut Vec<JsonChange>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let path = json_path_key(path, key);
+                match new.get(key) {
+                    Some(new_value) => json_diff(old_value, new_value, &path, changes),
+                    None => changes.push(JsonChange {
+                        path,
+                        kind: ChangeKind::Removed(old_value.clone()),
+                    }),
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    changes.push(JsonChange {
+                        path: json_path_key(path, key),
+                        kind: ChangeKind::Added(new_value.clone()),
+                    });
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for index in 0..old.len().max(new.len()) {
+                let path = format!("{}[{}]", path, index);
+                match (old.get(index), new.get(index)) {
+                    (Some(old_value), Some(new_value)) => {
+                        json_diff(old_value, new_value, &path, changes)
+                    }
+                    (Some(old_value), None) => changes.push(JsonChange {
+                        path,
+                        kind: ChangeKind::Removed(old_value.clone()),
+                    }),
+                    (None, Some(new_value)) => changes.push(JsonChange {
+                        path,
+                        kind: ChangeKind::Added(new_value.clone()),
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        _ if old != new => changes.push(JsonChange {
+            path: path.to_string(),
+            kind: ChangeKind::Changed(old.clone(), new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+/// Lines of `old` and `new` aligned on their longest common subsequence.
+fn line_diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut lines: Vec<DiffLine> = old[..prefix]
+        .iter()
+        .map(|line| DiffLine::Same(line.to_string()))
+        .collect();
+    if old_middle.len() * new_middle.len() > MAX_LINE_PAIRS {
+        lines.extend(old_middle.iter().map(|l| DiffLine::Removed(l.to_string())));
+        lines.extend(new_middle.iter().map(|l| DiffLine::Added(l.to_string())));
+    } else {
+        lines.extend(lcs_diff(old_middle, new_middle));
+    }
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| DiffLine::Same(line.to_string())),
+    );
+    lines
+}
+
+fn lcs_diff(old: &[&str], new: &[&str]) -> Vec<DiffLine> {
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn json_changes(old: &str, new: &str) -> Vec<(String, String)> {
+        let Diff::Json(changes) = diff(old, new) else {
+            panic!("expected a JSON diff");
+        };
+        let mut changes: Vec<(String, String)> = changes
+            .into_iter()
+            .map(|change| {
+                let kind = match change.kind {
+                    ChangeKind::Added(new) => format!("+{}", new),
+                    ChangeKind::Removed(old) => format!("-{}", old),
+                    ChangeKind::Changed(old, new) => format!("{} -> {}", old, new),
+                };
+                (change.path, kind)
+            })
+            .collect();
+        changes.sort();
+        changes
+    }
+
+    fn lines(old: &str, new: &str) -> Vec<String> {
+        let Diff::Lines(lines) = diff(old, new) else {
+            panic!("expected a line diff");
+        };
+        lines
+            .into_iter()
+            .map(|line| match line {
+                DiffLine::Same(line) => format!(" {}", line),
+                DiffLine::Removed(line) => format!("-{}", line),
+                DiffLine::Added(line) => format!("+{}", line),
+            })
+            .collect()
+    }
+
+    fn change(path: &str, kind: &str) -> (String, String) {
+        (path.to_string(), kind.to_string())
+    }
+
+    #[test]
+    fn json_diff_compares_objects_by_key() {
+        let changes = json_changes(
+            r#"{"type": "chat", "user": {"id": 1, "name": "ann"}, "seq": 4}"#,
+            r#"{"type": "chat", "user": {"id": 2, "name": "ann"}, "ack": true}"#,
+        );
+        assert_eq!(
+            changes,
+            vec![
+                change("$.ack", "+true"),
+                change("$.seq", "-4"),
+                change("$.user.id", "1 -> 2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_diff_compares_arrays_by_index() {
+        let changes = json_changes(r#"[1, [2, 3], "x"]"#, r#"[1, [2, 4]]"#);
+        assert_eq!(
+            changes,
+            vec![change("$[1][1]", "3 -> 4"), change("$[2]", "-\"x\"")]
+        );
+    }
+
+    #[test]
+    fn json_diff_replaces_values_of_another_type() {
+        let changes = json_changes(r#"{"data": [1]}"#, r#"{"data": {"0": 1}}"#);
+        assert_eq!(
+            changes,
+            vec![change(
+                "$.data",
+                &format!("{} -> {}", json!([1]), json!({"0": 1}))
+            )]
+        );
+    }
+
+    #[test]
+    fn json_diff_quotes_keys_that_are_not_identifiers() {
+        let changes = json_changes(r#"{"a b": 1}"#, r#"{"a b": 2}"#);
+        assert_eq!(changes, vec![change("$['a b']", "1 -> 2")]);
+    }
+
+    #[test]
+    fn equal_json_has_no_changes() {
+        assert!(json_changes(r#"{"a": [1, 2]}"#, r#"{ "a" : [1,2] }"#).is_empty());
+    }
+
+    #[test]
+    fn line_diff_aligns_common_lines() {
+        assert_eq!(
+            lines("a\nb\nc\nd", "a\nx\nc\nd\ne"),
+            vec![" a", "-b", "+x", " c", " d", "+e"]
+        );
+    }
+
+    #[test]
+    fn line_diff_is_used_when_one_side_is_not_json() {
+        assert_eq!(lines("{\"a\": 1}", "plain"), vec!["-{\"a\": 1}", "+plain"]);
+    }
+
+    #[test]
+    fn line_diff_of_equal_text_is_unchanged() {
+        assert_eq!(lines("one\ntwo", "one\ntwo"), vec![" one", " two"]);
+    }
+
+    #[test]
+    fn line_diff_gives_up_aligning_huge_changes() {
+        let old: Vec<String> = (0..2001).map(|i| format!("old {}", i)).collect();
+        let new: Vec<String> = (0..2001).map(|i| format!("new {}", i)).collect();
+        let diff = lines(&old.join("\n"), &new.join("\n"));
+        assert_eq!(diff.len(), 4002);
+        assert!(diff[..2001].iter().all(|line| line.starts_with('-')));
+        assert!(diff[2001..].iter().all(|line| line.starts_with('+')));
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::RwLock;
+
+use crate::structs::Environment;
+use crate::utils::to_hex_hiding;
+
+// Shown instead of secret values
+pub const MASK: &str = "••••••";
+
+// Variables of the active environment, read by `{{env.NAME}}` templates from any task
+static VARIABLES: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+static SECRET_VALUES: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Makes the variables of `environment` available to templates, replacing the previous ones.
+/// `None` leaves only the process environment.
+pub fn activate(environment: Option<&Environment>) {
+    let mut variables = BTreeMap::new();
+    let mut secret_values = Vec::new();
+    if let Some(environment) = environment {
+        variables.extend(environment.variables.clone());
+        variables.extend(environment.secrets.clone());
+        secret_values.extend(
+            environment
+                .secrets
+                .values()
+                .filter(|v| !v.is_empty())
+                .cloned(),
+        );
+    }
+    // Longest first so a secret containing another one is masked whole
+    secret_values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+    *VARIABLES.write().unwrap() = variables;
+    *SECRET_VALUES.write().unwrap() = secret_values;
+}
+
+/// Value of `name` in the active environment, falling back to the process environment.
+pub fn lookup(name: &str) -> Option<String> {
+    match VARIABLES.read().unwrap().get(name) {
+        Some(value) => Some(value.clone()),
+        None => std::env::var(name).ok(),
+    }
+}
+
+/// Replaces the values of the active environment's secrets in `text` so it can be shown or
+/// logged.
+pub fn mask(text: &str) -> String {
+    let secret_values = SECRET_VALUES.read().unwrap();
+    secret_values.iter().fold(text.to_string(), |text, value| {
+        text.replace(value.as_str(), MASK)
+    })
+}
+
+/// Byte ranges of `data` holding the values of the active environment's secrets, for views
+/// of binary payloads to hide. The payload itself is kept as it is.
+pub fn secret_ranges(data: &[u8]) -> Vec<Range<usize>> {
+    let secret_values = SECRET_VALUES.read().unwrap();
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    // Longest first, so a secret inside another one is part of the longer range
+    for value in secret_values.iter().map(|value| value.as_bytes()) {
+        let mut start = 0;
+        while let Some(offset) = data[start..]
+            .windows(value.len())
+            .position(|window| window == value)
+        {
+            let range = start + offset..start + offset + value.len();
+            if !ranges
+                .iter()
+                .any(|r| r.start < range.end && range.start < r.end)
+            {
+                ranges.push(range.clone());
+            }
+            start = range.end;
+        }
+    }
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+/// `to_hex` of `data` with the bytes of secrets shown as `**`.
+pub fn masked_hex(data: &[u8]) -> String {
+    to_hex_hiding(data, &secret_ranges(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_hidden_in_binary_views_only() {
+        let mut environment = Environment::default();
+        environment
+            .secrets
+            .insert(String::from("TOKEN"), String::from("k3y-5ecret"));
+        activate(Some(&environment));
+        let data = [&[0xff, 0x00][..], b"k3y-5ecret", &[0x01]].concat();
+        assert_eq!(secret_ranges(&data), vec![2..12]);
+        assert_eq!(masked_hex(&data), "ff 00 ** ** ** ** ** ** ** ** ** ** 01");
+        activate(None);
+        assert!(secret_ranges(&data).is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+use crate::structs::{ExtractionKind, ExtractionRule};
+
+/// The compiled expression of an `ExtractionRule`.
+#[derive(Debug, Clone)]
+pub enum Extractor {
+    JsonPath(JsonPath),
+    Regex(Regex),
+}
+
+fn compile(rule: &ExtractionRule) -> Result<Extractor> {
+    Ok(match rule.kind {
+        ExtractionKind::JsonPath => {
+            Extractor::JsonPath(JsonPath::parse(&rule.expression).map_err(|e| anyhow!("{}", e))?)
+        }
+        ExtractionKind::Regex => Extractor::Regex(Regex::new(&rule.expression)?),
+    })
+}
+
+/// Checks that the rule's expression compiles, so broken rules are rejected when added, and
+/// keeps the compiled expression with the rule.
+pub fn validate_rule(rule: &ExtractionRule) -> Result<()> {
+    if rule.variable.trim().is_empty() {
+        return Err(anyhow!("Variable name is empty"));
+    }
+    let extractor = compile(rule)?;
+    let _ = rule.extractor.set(Some(extractor));
+    Ok(())
+}
+
+/// Applies every rule to an incoming payload and returns the `(variable, value)` pairs that
+/// matched. Regex rules capture the first group (or the whole match when there is none);
+/// JSONPath rules take the first node, with strings unquoted.
+pub fn extract(rules: &[ExtractionRule], payload: &str) -> Vec<(String, String)> {
+    let json: Option<Value> = serde_json::from_str(payload).ok();
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let extractor = rule.extractor.get_or_init(|| compile(rule).ok());
+            let value = match extractor.as_ref()? {
+                Extractor::JsonPath(path) => match path.query(json.as_ref()?).first()? {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                },
+                Extractor::Regex(regex) => {
+                    let caps = regex.captures(payload)?;
+                    caps.get(1).or_else(|| caps.get(0))?.as_str().to_string()
+                }
+            };
+            Some((rule.variable.clone(), value))
+        })
+        .collect()
+}
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use regex::{Regex, RegexBuilder};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+use crate::history::{Direction, HistorySearch, LogEntry};
+use crate::structs::{FilterKind, MessageFilter, SearchHit};
+
+// Match results kept per entry before the cache starts over
+const MAX_CACHED_MATCHES: usize = 10_000;
+// Results kept per connection by the search across all connections
+const SEARCH_LIMIT: usize = 200;
+
+/// A `MessageFilter` checked and compiled once, then matched against many entries.
+#[derive(Debug)]
+pub struct CompiledFilter {
+    matcher: Option<Matcher>,
+    direction: Option<Direction>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Regex(Regex),
+    JsonPath(JsonPath),
+}
+
+impl CompiledFilter {
+    pub fn new(filter: &MessageFilter) -> Result<Self> {
+        let query = filter.query.trim();
+        let matcher = match (filter.kind, query.is_empty()) {
+            (_, true) => None,
+            (FilterKind::Text, false) => Some(Matcher::Regex(
+                RegexBuilder::new(&regex::escape(query))
+                    .case_insensitive(true)
+                    .build()?,
+            )),
+            (FilterKind::Regex, false) => Some(Matcher::Regex(Regex::new(query)?)),
+            (FilterKind::JsonPath, false) => Some(Matcher::JsonPath(
+                JsonPath::parse(query).map_err(|e| anyhow!("{}", e))?,
+            )),
+        };
+        Ok(Self {
+            matcher,
+            direction: filter.direction,
+            from: parse_time(&filter.from)?,
+            to: parse_time(&filter.to)?,
+        })
+    }
+
+    /// Whether the filter lets every entry through.
+    pub fn is_empty(&self) -> bool {
+        self.matcher.is_none()
+            && self.direction.is_none()
+            && self.from.is_none()
+            && self.to.is_none()
+    }
+
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        if self.direction.is_some_and(|d| d != entry.direction)
+            || self.from.is_some_and(|from| entry.timestamp < from)
+            || self.to.is_some_and(|to| entry.timestamp > to)
+        {
+            return false;
+        }
+        match &self.matcher {
+            None => true,
+            Some(Matcher::Regex(regex)) => regex.is_match(&entry.text),
+            // A JSONPath query matches the messages it selects at least one node of
+            Some(Matcher::JsonPath(path)) => serde_json::from_str::<Value>(&entry.text)
+                .is_ok_and(|value| !path.query(&value).all().is_empty()),
+        }
+    }
+
+    /// Byte ranges of `text` to highlight, empty for JSONPath queries.
+    pub fn highlights(&self, text: &str) -> Vec<Range<usize>> {
+        match &self.matcher {
+            Some(Matcher::Regex(regex)) => regex
+                .find_iter(text)
+                .filter(|found| !found.is_empty())
+                .map(|found| found.range())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Searches the histories of several connections, reading their files, and returns the
+/// matches ordered by time with the last error met.
+pub fn search(
+    filter: &CompiledFilter,
+    histories: Vec<(u8, HistorySearch)>,
+) -> (Vec<SearchHit>, Option<String>) {
+    let mut results = Vec::new();
+    let mut error = None;
+    for (conn, history) in histories {
+        match history.find(|entry| filter.matches(entry), SEARCH_LIMIT) {
+            Ok(found) => results.extend(found.into_iter().map(|(index, entry)| SearchHit {
+                conn,
+                index,
+                entry,
+            })),
+            Err(e) => error = Some(e.to_string()),
+        }
+    }
+    results.sort_by_key(|hit: &SearchHit| hit.entry.timestamp);
+    (results, error)
+}
+
+/// Parses a local `YYYY-MM-DD HH:MM[:SS]`, or `HH:MM[:SS]` for today. Empty means no bound.
+fn parse_time(text: &str) -> Result<Option<DateTime<Utc>>> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| {
+            ["%H:%M:%S", "%H:%M"]
+                .iter()
+                .find_map(|format| NaiveTime::parse_from_str(text, format).ok())
+                .map(|time| Local::now().date_naive().and_time(time))
+        });
+    let Some(naive) = naive else {
+        bail!(
+            "Invalid time {}, expected HH:MM[:SS] or YYYY-MM-DD HH:MM[:SS]",
+            text
+        );
+    };
+    match Local.from_local_datetime(&naive).earliest() {
+        Some(time) => Ok(Some(time.with_timezone(&Utc))),
+        None => bail!("{} does not exist in the local time zone", text),
+    }
+}
+
+/// The compiled filter of a connection window, compiled again only when the filter changes.
+/// Whether an entry matches is kept by its index in the history, so JSON messages are not
+/// parsed again on every repaint.
+#[derive(Default)]
+pub struct FilterCache {
+    source: Option<MessageFilter>,
+    compiled: Option<CompiledFilter>,
+    error: Option<String>,
+    matches: HashMap<usize, bool>,
+}
+
+impl FilterCache {
+    /// Compiles `filter` if it changed since the last call.
+    pub fn update(&mut self, filter: &MessageFilter) {
+        if self.source.as_ref() == Some(filter) {
+            return;
+        }
+        self.source = Some(filter.clone());
+        self.matches.clear();
+        match CompiledFilter::new(filter) {
+            Ok(compiled) => {
+                self.compiled = Some(compiled).filter(|compiled| !compiled.is_empty());
+                self.error = None;
+            }
+            Err(e) => {
+                self.compiled = None;
+                self.error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// The filter, `None` when it lets every entry through or does not compile.
+    pub fn compiled(&self) -> Option<&CompiledFilter> {
+        self.compiled.as_ref()
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Whether the entry at `index` of the history matches.
+    pub fn matches(&mut self, index: usize, entry: &LogEntry) -> bool {
+        let Some(compiled) = &self.compiled else {
+            return true;
+        };
+        if self.matches.len() >= MAX_CACHED_MATCHES {
+            self.matches.clear();
+        }
+        *self
+            .matches
+            .entry(index)
+            .or_insert_with(|| compiled.matches(entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn local(date: NaiveDate, time: &str) -> DateTime<Utc> {
+        let time = NaiveTime::parse_from_str(time, "%H:%M:%S").unwrap();
+        Local
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn empty_time_is_no_bound() {
+        assert_eq!(parse_time("").unwrap(), None);
+        assert_eq!(parse_time("   ").unwrap(), None);
+    }
+
+    #[test]
+    fn parses_local_date_and_time() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        assert_eq!(
+            parse_time("2024-05-01 08:15:30").unwrap(),
+            Some(local(date, "08:15:30"))
+        );
+        assert_eq!(
+            parse_time(" 2024-05-01 08:15 ").unwrap(),
+            Some(local(date, "08:15:00"))
+        );
+    }
+
+    #[test]
+    fn time_alone_is_today() {
+        for (text, time) in [("12:30", "12:30:00"), ("23:59:59", "23:59:59")] {
+            let before = Local::now().date_naive();
+            let parsed = parse_time(text).unwrap();
+            let after = Local::now().date_naive();
+            // Either day when the test runs across midnight
+            assert!(
+                parsed == Some(local(before, time)) || parsed == Some(local(after, time)),
+                "{} parsed as {:?}",
+                text,
+                parsed
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        for text in [
+            "yesterday",
+            "2024-05-01",
+            "25:00",
+            "12:30pm",
+            "2024/05/01 08:15",
+        ] {
+            assert!(parse_time(text).is_err(), "{} was accepted", text);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+use crate::diff::{json_diff, ChangeKind};
+
+/// Checks that `pointer` is a JSON pointer such as `/data/0/id`. A `*` segment matches any
+/// key or index, e.g. `/items/*/updated_at`.
+pub fn validate_pointer(pointer: &str) -> Result<()> {
+    if !pointer.starts_with('/') {
+        bail!("A JSON pointer starts with '/': {}", pointer);
+    }
+    Ok(())
+}
+
+/// Compares a recorded text frame with a replayed one. Returns `None` when they match once the
+/// `ignore` pointers are removed from both, otherwise one line per changed JSONPath. Frames that
+/// are not JSON must be equal, a difference between them is reported without changes.
+pub fn compare_frames(expected: &str, actual: &str, ignore: &[String]) -> Option<Vec<String>> {
+    if expected == actual {
+        return None;
+    }
+    let (Ok(mut expected), Ok(mut actual)) = (
+        serde_json::from_str::<Value>(expected),
+        serde_json::from_str::<Value>(actual),
+    ) else {
+        return Some(Vec::new());
+    };
+    for pointer in ignore {
+        let segments: Vec<String> = pointer
+            .split('/')
+            .skip(1)
+            .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+            .collect();
+        remove_at(&mut expected, &segments);
+        remove_at(&mut actual, &segments);
+    }
+    let mut changes = Vec::new();
+    json_diff(&expected, &actual, "$", &mut changes);
+    let changes: Vec<String> = changes
+        .into_iter()
+        .map(|change| match change.kind {
+            ChangeKind::Added(value) => format!("{}: added {}", change.path, value),
+            ChangeKind::Removed(value) => format!("{}: removed, was {}", change.path, value),
+            ChangeKind::Changed(old, new) => format!("{}: {} -> {}", change.path, old, new),
+        })
+        .collect();
+    (!changes.is_empty()).then_some(changes)
+}
+
+fn remove_at(value: &mut Value, segments: &[String]) {
+    let Some((segment, rest)) = segments.split_first() else {
+        return;
+    };
+    match value {
+        Value::Object(map) => match (segment.as_str(), rest.is_empty()) {
+            ("*", true) => map.clear(),
+            ("*", false) => map.values_mut().for_each(|value| remove_at(value, rest)),
+            (key, true) => {
+                map.remove(key);
+            }
+            (key, false) => {
+                if let Some(value) = map.get_mut(key) {
+                    remove_at(value, rest);
+                }
+            }
+        },
+        // Ignored array elements become null so the following ones keep their index
+        Value::Array(items) => match (segment.as_str(), rest.is_empty()) {
+            ("*", true) => items.iter_mut().for_each(|item| *item = Value::Null),
+            ("*", false) => items.iter_mut().for_each(|item| remove_at(item, rest)),
+            (index, last) => {
+                if let Some(item) = index.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                    if last {
+                        *item = Value::Null;
+                    } else {
+                        remove_at(item, rest);
+                    }
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignore(pointers: &[&str]) -> Vec<String> {
+        pointers.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn equal_frames_match() {
+        assert_eq!(compare_frames("pong", "pong", &[]), None);
+        assert_eq!(compare_frames(r#"{"a": 1}"#, r#"{ "a":1 }"#, &[]), None);
+    }
+
+    #[test]
+    fn different_text_frames_have_no_changes() {
+        assert_eq!(compare_frames("pong", "ping", &[]), Some(Vec::new()));
+    }
+
+    #[test]
+    fn reports_changed_paths() {
+        let changes = compare_frames(
+            r#"{"id": 1, "items": [{"n": 1}], "old": true}"#,
+            r#"{"id": 2, "items": [{"n": 1}, {"n": 2}], "new": "x"}"#,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                "$.id: 1 -> 2",
+                "$.items[1]: added {\"n\":2}",
+                "$.old: removed, was true",
+                "$.new: added \"x\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_pointers() {
+        let expected = r#"{"id": 1, "at": "10:00", "body": "hi"}"#;
+        let actual = r#"{"id": 2, "at": "11:00", "body": "hi"}"#;
+        assert_eq!(
+            compare_frames(expected, actual, &ignore(&["/id", "/at"])),
+            None
+        );
+        assert_eq!(
+            compare_frames(expected, actual, &ignore(&["/at"])),
+            Some(vec![String::from("$.id: 1 -> 2")])
+        );
+    }
+
+    #[test]
+    fn wildcard_matches_any_key() {
+        let expected = r#"{"users": {"ann": {"seen": 1, "id": 7}, "bob": {"seen": 2, "id": 8}}}"#;
+        let actual = r#"{"users": {"ann": {"seen": 3, "id": 7}, "bob": {"seen": 4, "id": 9}}}"#;
+        assert_eq!(
+            compare_frames(expected, actual, &ignore(&["/users/*/seen"])),
+            Some(vec![String::from("$.users.bob.id: 8 -> 9")])
+        );
+    }
+
+    #[test]
+    fn wildcard_matches_any_index() {
+        let expected = r#"{"items": [{"id": 1, "updated_at": 5}, {"id": 2, "updated_at": 6}]}"#;
+        let actual = r#"{"items": [{"id": 1, "updated_at": 7}, {"id": 2, "updated_at": 8}]}"#;
+        assert_eq!(
+            compare_frames(expected, actual, &ignore(&["/items/*/updated_at"])),
+            None
+        );
+    }
+
+    #[test]
+    fn trailing_wildcard_ignores_every_member() {
+        assert_eq!(
+            compare_frames(
+                r#"{"meta": {"a": 1}, "list": [1, 2], "x": 0}"#,
+                r#"{"meta": {"b": 2}, "list": [3, 4], "x": 0}"#,
+                &ignore(&["/meta/*", "/list/*"])
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn ignored_array_elements_keep_the_others_in_place() {
+        assert_eq!(
+            compare_frames("[1, 2, 3]", "[9, 2, 4]", &ignore(&["/0"])),
+            Some(vec![String::from("$[2]: 3 -> 4")])
+        );
+    }
+
+    #[test]
+    fn escaped_pointer_segments() {
+        assert_eq!(
+            compare_frames(
+                r#"{"a/b": 1, "c~d": 1}"#,
+                r#"{"a/b": 2, "c~d": 2}"#,
+                &ignore(&["/a~1b", "/c~0d"])
+            ),
+            None
+        );
+    }
+}
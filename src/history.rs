@@ -0,0 +1,334 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use anyhow::Result;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::environment::{mask, masked_hex};
+use crate::persistence::data_dir;
+
+// Entries kept in memory per connection, older ones are only read back from disk
+const RING_CAPACITY: usize = 1000;
+// The byte offset of every INDEX_STRIDE-th entry is kept so pages are read without a full scan
+const INDEX_STRIDE: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Sent,
+    Received,
+    /// A note about the connection, e.g. script output or triage progress
+    Info,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Opcode {
+    Text,
+    Binary,
+    Ping,
+    Pong,
+    Close,
+}
+
+/// An entry of a connection's message log, and one line of its history file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub direction: Direction,
+    pub timestamp: DateTime<Utc>,
+    /// Microseconds since the log was created, from a monotonic clock
+    pub elapsed_us: u64,
+    /// `None` for `Direction::Info` entries
+    pub opcode: Option<Opcode>,
+    /// Payload size in bytes
+    pub size: usize,
+    /// Payload of binary frames, the payload of other entries is `text`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub data: Vec<u8>,
+    /// Decoded payload, binary frames that are not UTF-8 are shown as hex
+    pub text: String,
+    /// Codec that decoded `text` from `data`, e.g. `msgpack`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+}
+
+/// Message history of a connection. The latest entries are kept in a bounded ring and every
+/// entry is appended to `<data dir>/history/<session>/connection-<id>.jsonl`, one JSON object
+/// per line.
+/// A log without a path, e.g. one created by `Default`, only keeps the ring.
+#[derive(Default)]
+pub struct MessageLog {
+    started: Option<Instant>,
+    recent: VecDeque<LogEntry>,
+    total: usize,
+    sent: usize,
+    received: usize,
+    path: Option<PathBuf>,
+    file: Option<File>,
+    index: Vec<u64>,
+    written: u64,
+    write_failed: bool,
+}
+
+impl MessageLog {
+    pub fn new(conn_id: u8) -> Self {
+        Self {
+            started: Some(Instant::now()),
+            path: Some(session_dir().join(format!("connection-{}.jsonl", conn_id))),
+            ..Default::default()
+        }
+    }
+
+    pub fn push_text(&mut self, direction: Direction, text: String) {
+        self.push(
+            direction,
+            Some(Opcode::Text),
+            text.len(),
+            Vec::new(),
+            text,
+            None,
+        );
+    }
+
+    pub fn push_binary(&mut self, direction: Direction, data: Vec<u8>) {
+        let text = match std::str::from_utf8(&data) {
+            Ok(text) => text.to_string(),
+            Err(_) => masked_hex(&data),
+        };
+        self.push(
+            direction,
+            Some(Opcode::Binary),
+            data.len(),
+            data,
+            text,
+            None,
+        );
+    }
+
+    /// Logs a binary frame `codec` decoded to `text`.
+    pub fn push_decoded(&mut self, direction: Direction, data: Vec<u8>, codec: &str, text: String) {
+        let codec = Some(codec.to_string());
+        self.push(
+            direction,
+            Some(Opcode::Binary),
+            data.len(),
+            data,
+            text,
+            codec,
+        );
+    }
+
+    /// Logs a ping, pong or close frame described by `text`.
+    pub fn push_control(&mut self, direction: Direction, opcode: Opcode, text: String) {
+        self.push(direction, Some(opcode), 0, Vec::new(), text, None);
+    }
+
+    pub fn push_info(&mut self, text: String) {
+        self.push(Direction::Info, None, 0, Vec::new(), text, None);
+    }
+
+    /// Logs an entry stamped with the current time, with the active environment's secrets
+    /// masked in its text. Binary payloads are kept as they are, views of them hide the secrets.
+    fn push(
+        &mut self,
+        direction: Direction,
+        opcode: Option<Opcode>,
+        size: usize,
+        data: Vec<u8>,
+        text: String,
+        codec: Option<String>,
+    ) {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        let entry = LogEntry {
+            direction,
+            timestamp: Utc::now(),
+            elapsed_us: started.elapsed().as_micros() as u64,
+            opcode,
+            size,
+            data,
+            text: mask(&text),
+            codec,
+        };
+        if let Err(e) = self.append(&entry) {
+            eprintln!("Could not write message history: {:?}", e);
+            // Keep going in memory only, the entries already on disk stay readable
+            self.file = None;
+            self.write_failed = true;
+        }
+        if self.recent.len() == RING_CAPACITY {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(entry);
+        self.total += 1;
+        match direction {
+            Direction::Sent => self.sent += 1,
+            Direction::Received => self.received += 1,
+            Direction::Info => {}
+        }
+    }
+
+    /// Number of entries logged, including the ones no longer held in memory.
+    pub fn len(&self) -> usize {
+        self.total
+    }
+
+    /// Number of frames sent and received, notes about the connection are not counted.
+    pub fn frame_counts(&self) -> (usize, usize) {
+        (self.sent, self.received)
+    }
+
+    /// The latest frame sent or received that is still held in memory.
+    pub fn last_frame(&self) -> Option<&LogEntry> {
+        self.recent
+            .iter()
+            .rev()
+            .find(|entry| entry.direction != Direction::Info)
+    }
+
+    /// Index of the oldest entry still held in memory.
+    pub fn first_recent(&self) -> usize {
+        self.total - self.recent.len()
+    }
+
+    pub fn recent(&self) -> impl Iterator<Item = &LogEntry> {
+        self.recent.iter()
+    }
+
+    /// Entries `start..start + count`, read from disk when they are older than the ring.
+    pub fn page(&self, start: usize, count: usize) -> Vec<LogEntry> {
+        let end = (start + count).min(self.total);
+        let first_recent = self.first_recent();
+        if start >= first_recent {
+            return self
+                .recent
+                .range(start - first_recent..end - first_recent)
+                .cloned()
+                .collect();
+        }
+        match self.read_page(start, end) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("Could not read message history: {:?}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// What a search of the whole history needs, so it can run without holding the log.
+    pub fn search(&self) -> HistorySearch {
+        HistorySearch {
+            path: self.path.clone(),
+            start: self.index.first().copied(),
+            total: self.total,
+            first_recent: self.first_recent(),
+            // Entries are only missing from the file when it could not be written
+            recent: if self.path.is_none() || self.write_failed {
+                self.recent.iter().cloned().collect()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    fn append(&mut self, entry: &LogEntry) -> Result<()> {
+        let Some(path) = self.path.as_ref().filter(|_| !self.write_failed) else {
+            return Ok(());
+        };
+        if self.file.is_none() {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            // A reused connection id appends to the existing file of this session
+            self.written = file.metadata()?.len();
+            self.file = Some(file);
+        }
+
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        if self.total.is_multiple_of(INDEX_STRIDE) {
+            self.index.push(self.written);
+        }
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(line.as_bytes())?;
+        }
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    fn read_page(&self, start: usize, end: usize) -> Result<Vec<LogEntry>> {
+        let checkpoint = start / INDEX_STRIDE;
+        let (Some(path), Some(offset)) = (&self.path, self.index.get(checkpoint)) else {
+            return Ok(Vec::new());
+        };
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(*offset))?;
+        BufReader::new(file)
+            .lines()
+            .skip(start - checkpoint * INDEX_STRIDE)
+            .take(end - start)
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+            .collect()
+    }
+}
+
+/// A connection's message history as it was when the search started.
+#[derive(Debug)]
+pub struct HistorySearch {
+    path: Option<PathBuf>,
+    /// Offset of the first entry in the history file
+    start: Option<u64>,
+    total: usize,
+    first_recent: usize,
+    recent: Vec<LogEntry>,
+}
+
+impl HistorySearch {
+    /// The first `limit` entries matching `predicate` with their index, oldest first. The
+    /// whole history is searched, reading the entries no longer in memory from disk.
+    pub fn find(
+        &self,
+        predicate: impl Fn(&LogEntry) -> bool,
+        limit: usize,
+    ) -> Result<Vec<(usize, LogEntry)>> {
+        let mut found = Vec::new();
+        let mut read = 0;
+        if let (Some(path), Some(offset)) = (&self.path, self.start) {
+            let mut file = File::open(path)?;
+            file.seek(SeekFrom::Start(offset))?;
+            for line in BufReader::new(file).lines().take(self.total) {
+                let entry: LogEntry = serde_json::from_str(&line?)?;
+                if predicate(&entry) {
+                    found.push((read, entry));
+                }
+                read += 1;
+                if found.len() == limit {
+                    return Ok(found);
+                }
+            }
+        }
+        // Entries that could not be written are only in memory
+        for (offset, entry) in self.recent.iter().enumerate() {
+            let index = self.first_recent + offset;
+            if index >= read && predicate(entry) {
+                found.push((index, entry.clone()));
+                if found.len() == limit {
+                    break;
+                }
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// Directory of this run's history files, named after the time the app started.
+fn session_dir() -> PathBuf {
+    static SESSION: OnceLock<String> = OnceLock::new();
+    let session = SESSION.get_or_init(|| Local::now().format("%Y-%m-%d_%H-%M-%S").to_string());
+    data_dir().join("history").join(session)
+}
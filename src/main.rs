@@ -1,26 +1,52 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
+mod autoresponder;
+mod cli;
+mod codec;
+mod diff;
+mod environment;
+mod extraction;
+mod filter;
+mod golden;
+mod history;
+mod migrations;
 mod networking;
 mod persistence;
+mod recording;
+mod report;
+mod scenario;
+mod scripting;
+mod snippets;
 mod structs;
+mod templating;
+mod triage;
 mod ui;
 mod utils;
+mod workspace;
 
 use std::sync::{Arc, Mutex};
 
+use clap::Parser;
+use cli::Cli;
 use networking::network_processor;
 use persistence::get_stored_app;
-use structs::{AppState, Message};
+use structs::Message;
 use tokio::sync::mpsc;
 
 #[tokio::main]
 async fn main() -> Result<(), eframe::Error> {
-    let (mut ui2ntx, mut ui2nrx) = mpsc::channel::<Message>(12);
-    let (mut n2uitx, mut ntuirx) = mpsc::channel::<Message>(200);
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        std::process::exit(cli::run(command, cli.env).await);
+    }
+
+    let (ui2ntx, mut ui2nrx) = mpsc::channel::<Message>(12);
+    let (mut n2uitx, ntuirx) = mpsc::channel::<Message>(200);
     tokio::spawn(async move {
         network_processor(&mut ui2nrx, &mut n2uitx).await;
     });
     let stored_state = get_stored_app();
+    environment::activate(stored_state.environment());
     let app_state = Arc::new(Mutex::new(stored_state));
     let ui = ui::UI::new(app_state, ui2ntx, ntuirx);
     ui.run().await
@@ -0,0 +1,138 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+/// Version of the saved state written by this build. When a change to `AppState` would not
+/// load from the previous format, bump it and append the migration to `MIGRATIONS`. Fields
+/// with `#[serde(default)]` need no migration.
+pub const STATE_VERSION: u64 = 2;
+
+// MIGRATIONS[n] upgrades a state of version n to version n + 1
+const MIGRATIONS: [fn(&mut Value) -> Result<()>; STATE_VERSION as usize] =
+    [drop_message_logs, enable_saved_scripts];
+
+/// Version of a saved state, files written before versioning are version 0.
+pub fn version_of(state: &Value) -> u64 {
+    state.get("version").and_then(Value::as_u64).unwrap_or(0)
+}
+
+/// Upgrades `state` to `STATE_VERSION` by running the migrations after its version in order.
+pub fn migrate(state: &mut Value) -> Result<()> {
+    let version = version_of(state);
+    if !state.is_object() {
+        bail!("The saved state is not a JSON object");
+    }
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(state).map_err(|e| e.context(format!("migrating from version {}", from)))?;
+        state["version"] = Value::from(from as u64 + 1);
+    }
+    Ok(())
+}
+
+/// Version 0 saved every connection's messages, they are kept in the history files since.
+fn drop_message_logs(state: &mut Value) -> Result<()> {
+    if let Some(windows) = state.get_mut("connections").and_then(Value::as_array_mut) {
+        for window in windows {
+            if let Some(connection) = window.get_mut("connection").and_then(Value::as_object_mut) {
+                connection.remove("messages");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Version 1 did not save whether a connection's script was attached, a saved script was
+/// attached again by hand after every start.
+fn enable_saved_scripts(state: &mut Value) -> Result<()> {
+    if let Some(windows) = state.get_mut("connections").and_then(Value::as_array_mut) {
+        for window in windows {
+            if let Some(connection) = window.get_mut("connection").and_then(Value::as_object_mut) {
+                let has_script = connection
+                    .get("script")
+                    .and_then(Value::as_str)
+                    .is_some_and(|script| !script.is_empty());
+                connection.insert(String::from("script_enabled"), Value::from(has_script));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn version_0_drops_message_logs() {
+        let mut state = json!({
+            "connections": [
+                {"id": 1, "connection": {"url": "ws://a", "messages": ["hello", "world"]}},
+                {"id": 2, "connection": {"url": "ws://b"}}
+            ],
+            "editing_ip": "ws://a"
+        });
+        assert_eq!(version_of(&state), 0);
+        migrate(&mut state).unwrap();
+        assert_eq!(
+            state,
+            json!({
+                "connections": [
+                    {"id": 1, "connection": {"url": "ws://a", "script_enabled": false}},
+                    {"id": 2, "connection": {"url": "ws://b", "script_enabled": false}}
+                ],
+                "editing_ip": "ws://a",
+                "version": 2
+            })
+        );
+    }
+
+    #[test]
+    fn version_1_enables_saved_scripts() {
+        let mut state = json!({
+            "version": 1,
+            "connections": [
+                {"id": 1, "connection": {"url": "ws://a", "script": "fn on_message(m) {}"}},
+                {"id": 2, "connection": {"url": "ws://b", "script": ""}}
+            ]
+        });
+        migrate(&mut state).unwrap();
+        assert_eq!(
+            state,
+            json!({
+                "version": 2,
+                "connections": [
+                    {"id": 1, "connection": {
+                        "url": "ws://a",
+                        "script": "fn on_message(m) {}",
+                        "script_enabled": true
+                    }},
+                    {"id": 2, "connection": {"url": "ws://b", "script": "", "script_enabled": false}}
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn current_version_is_left_alone() {
+        let mut state = json!({
+            "version": STATE_VERSION,
+            "connections": [{"connection": {"messages": ["kept"]}}]
+        });
+        let before = state.clone();
+        migrate(&mut state).unwrap();
+        assert_eq!(state, before);
+    }
+
+    #[test]
+    fn state_without_connections_migrates() {
+        let mut state = json!({});
+        migrate(&mut state).unwrap();
+        assert_eq!(state, json!({"version": 2}));
+    }
+
+    #[test]
+    fn rejects_state_that_is_not_an_object() {
+        assert!(migrate(&mut json!([1, 2])).is_err());
+    }
+}
@@ -1,99 +1,627 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::mpsc::{self, Receiver, Sender};
 
-use crate::structs::Message;
+use crate::autoresponder::match_frame;
+use crate::filter::search;
+use crate::recording::{
+    record, record_opening, replay_session, Opening, RecordedKind, SharedRecorder,
+};
+use crate::scenario::run_scenario;
+use crate::scripting::{ScriptEffect, ScriptHost};
+use crate::structs::{AutoResponder, Message};
+use crate::templating::{render_template, TemplateContext};
+use crate::triage::triage_crash;
 use anyhow::Result;
 use futures_util::sink::SinkExt;
 use futures_util::{stream::SplitSink, StreamExt};
 use tokio::net::TcpStream;
 use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tungstenite::protocol::CloseFrame;
+
+pub type WsSink = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, tungstenite::Message>;
+
+const SCRIPT_TICK: Duration = Duration::from_secs(1);
+
+/// The outcome of connecting a client: its id, URL and connect number, with the task's commands.
+type Connected = (
+    u8,
+    String,
+    u64,
+    Result<(Sender<ConnectionCommand>, Opening), anyhow::Error>,
+);
+
+/// Instructions for the task that owns a client connection.
+#[derive(Debug)]
+pub enum ConnectionCommand {
+    Send(String),
+    SendBinary(Vec<u8>),
+    Ping(Vec<u8>),
+    Close(Option<CloseFrame<'static>>),
+    SetScript(Option<String>),
+    SetAutoResponders(Vec<AutoResponder>),
+    SetVariables(HashMap<String, String>),
+}
 
 pub async fn network_processor(
     ui_to_network: &mut Receiver<Message>,
     network_to_ui: &mut Sender<Message>,
 ) {
-    let mut connection_map: HashMap<
-        u8,
-        SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, tungstenite::Message>,
-    > = HashMap::new();
+    let mut connection_map: HashMap<u8, Sender<ConnectionCommand>> = HashMap::new();
+    // How each connection was opened, recorded again when a recording starts
+    let mut openings: HashMap<u8, Opening> = HashMap::new();
+    let recorder: SharedRecorder = Arc::new(Mutex::new(None));
+    // Connects run on their own tasks so a slow handshake does not hold up other connections,
+    // and report back here. Each connect is numbered so the result of one that was closed or
+    // replaced meanwhile is told apart.
+    let (connected_tx, mut connected) = mpsc::channel::<Connected>(64);
+    let mut connecting: HashMap<u8, u64> = HashMap::new();
+    let mut attempts = 0;
 
     let net_to_ui = network_to_ui;
-    while let Some(message) = ui_to_network.recv().await {
-        println!("Network = {:?}", message);
-        match message {
-            Message::NewClient { id, ip } => {
-                let res = handle_new_client(net_to_ui.clone(), id.to_owned(), ip.to_owned()).await;
+    loop {
+        let message = tokio::select! {
+            message = ui_to_network.recv() => match message {
+                Some(message) => message,
+                None => break,
+            },
+            Some((id, ip, attempt, res)) = connected.recv() => {
+                let current = connecting.get(&id) == Some(&attempt);
+                if current {
+                    connecting.remove(&id);
+                }
                 match res {
-                    Ok(sender) => {
+                    Ok((sender, opening)) if current => {
                         connection_map.insert(id, sender);
+                        openings.insert(id, opening);
+                        let _ = net_to_ui.send(Message::NewClient { id, ip }).await;
                     }
-                    Err(err) => println!("{:?}", err),
-                }
-            }
-            Message::Message {
-                id,
-                payload,
-                num_bytes,
-            } => {
-                println!("id={}, payload={}, num_bytes={}", id, payload, num_bytes);
-                match connection_map.get_mut(&id) {
-                    Some(ws) => {
-                        let _ = ws.send(tungstenite::Message::Text(payload)).await;
+                    Ok((sender, _)) => {
+                        let _ = sender.send(ConnectionCommand::Close(None)).await;
+                    }
+                    Err(err) => {
+                        eprintln!("{:?}", err);
+                        if current {
+                            let _ = net_to_ui.send(Message::Closed { id, code: None }).await;
+                        }
                     }
-                    None => todo!(),
                 }
+                continue;
+            }
+        };
+        match message {
+            Message::NewClient { id, ip } => {
+                attempts += 1;
+                connecting.insert(id, attempts);
+                let (net_to_ui, recorder) = (net_to_ui.clone(), recorder.clone());
+                let connected_tx = connected_tx.clone();
+                let attempt = attempts;
+                tokio::spawn(async move {
+                    let res = handle_new_client(net_to_ui, recorder, id, ip.clone()).await;
+                    let _ = connected_tx.send((id, ip, attempt, res)).await;
+                });
             }
-            Message::Close { id } => {
-                if let Some(mut ws_sink) = connection_map.remove(&id) {
-                    let _ = ws_sink.send(tungstenite::Message::Close(None)).await;
-                    println!("Closed WebSocket for ID: {}", id);
+            Message::Payload { id, payload, .. } => match connection_map.get(&id) {
+                Some(commands) => {
+                    let _ = commands.send(ConnectionCommand::Send(payload)).await;
+                }
+                None => eprintln!("Failed to find WebSocket for ID: {}", id),
+            },
+            Message::Binary { id, data } => match connection_map.get(&id) {
+                Some(commands) => {
+                    let _ = commands.send(ConnectionCommand::SendBinary(data)).await;
+                }
+                None => eprintln!("Failed to find WebSocket for ID: {}", id),
+            },
+            Message::Ping { id, payload } => match connection_map.get(&id) {
+                Some(commands) => {
+                    let _ = commands.send(ConnectionCommand::Ping(payload)).await;
+                }
+                None => eprintln!("Failed to find WebSocket for ID: {}", id),
+            },
+            Message::Close { id, code, reason } => {
+                openings.remove(&id);
+                if connecting.remove(&id).is_some() {
+                    eprintln!("Cancelled connecting WebSocket for ID: {}", id);
+                } else if let Some(commands) = connection_map.remove(&id) {
+                    let frame = code.map(|code| CloseFrame {
+                        code: code.into(),
+                        reason: reason.into(),
+                    });
+                    let _ = commands.send(ConnectionCommand::Close(frame)).await;
+                    eprintln!("Closed WebSocket for ID: {}", id);
                 } else {
-                    println!("Failed to find WebSocket for ID: {}", id);
+                    eprintln!("Failed to find WebSocket for ID: {}", id);
                 }
             }
+            // Triage probes and scenario runs open connections of their own that are not
+            // recorded: a replay opens one connection per recorded id, so their frames would be
+            // mixed into the window's connection
+            Message::Triage { id, ip, input } => {
+                let net_to_ui = net_to_ui.clone();
+                tokio::spawn(async move {
+                    match triage_crash(ip, input).await {
+                        Ok(finding) => {
+                            let _ = net_to_ui.send(Message::TriageDone { id, finding }).await;
+                        }
+                        Err(err) => eprintln!("Triage failed for ID {}: {:?}", id, err),
+                    }
+                });
+            }
+            Message::Search { filter, histories } => {
+                let net_to_ui = net_to_ui.clone();
+                tokio::task::spawn_blocking(move || {
+                    let (results, error) = search(&filter, histories);
+                    let _ = net_to_ui.blocking_send(Message::SearchDone { results, error });
+                });
+            }
+            Message::RunScenario { id, ip, scenario } => {
+                tokio::spawn(run_scenario(scenario, ip, id, net_to_ui.clone()));
+            }
+            Message::SetScript { id, source } => match connection_map.get(&id) {
+                Some(commands) => {
+                    let _ = commands.send(ConnectionCommand::SetScript(source)).await;
+                }
+                None => eprintln!("Failed to find WebSocket for ID: {}", id),
+            },
+            Message::SetAutoResponders { id, responders } => match connection_map.get(&id) {
+                Some(commands) => {
+                    let _ = commands
+                        .send(ConnectionCommand::SetAutoResponders(responders))
+                        .await;
+                }
+                None => eprintln!("Failed to find WebSocket for ID: {}", id),
+            },
+            Message::SetVariables { id, variables } => match connection_map.get(&id) {
+                Some(commands) => {
+                    let _ = commands
+                        .send(ConnectionCommand::SetVariables(variables))
+                        .await;
+                }
+                None => eprintln!("Failed to find WebSocket for ID: {}", id),
+            },
+            Message::StartRecording { recorder: new } => {
+                *recorder.lock().unwrap() = Some(new);
+                for (id, commands) in &connection_map {
+                    // The task of a connection the server closed is gone
+                    if let (false, Some(opening)) = (commands.is_closed(), openings.get(id)) {
+                        record_opening(&recorder, *id, opening);
+                    }
+                }
+            }
+            Message::StopRecording => {
+                recorder.lock().unwrap().take();
+            }
+            Message::Replay {
+                golden,
+                events,
+                target,
+                speed,
+                ignore,
+            } => {
+                let net_to_ui = net_to_ui.clone();
+                tokio::spawn(async move {
+                    let report = replay_session(events, target, speed, ignore).await;
+                    let _ = net_to_ui.send(Message::ReplayDone { golden, report }).await;
+                });
+            }
+            Message::Closed { .. }
+            | Message::ReplayDone { .. }
+            | Message::TriageDone { .. }
+            | Message::SearchDone { .. }
+            | Message::ScenarioStep { .. }
+            | Message::ScenarioDone { .. }
+            | Message::Sent { .. }
+            | Message::ScriptLog { .. }
+            | Message::ScriptStatus { .. }
+            | Message::SetVariable { .. }
+            | Message::Pong { .. }
+            | Message::Handshake { .. } => {}
         }
     }
 }
 
 pub async fn handle_new_client(
     network_to_ui: Sender<Message>,
+    recorder: SharedRecorder,
     id: u8,
     ip: String,
-) -> Result<
-    SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, tungstenite::Message>,
-    anyhow::Error,
-> {
+) -> Result<(Sender<ConnectionCommand>, Opening), anyhow::Error> {
     let net_to_ui = network_to_ui;
     let url = url::Url::parse(&ip)?;
 
-    let (ws_stream, _) = connect_async(url).await?;
+    let (ws_stream, response) = connect_async(url).await?;
+    let headers: Vec<(String, String)> = response
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            (name.to_string(), value)
+        })
+        .collect();
+    let status = response.status().as_u16();
+    let opening = Opening {
+        url: ip,
+        status,
+        headers: headers.clone(),
+    };
+    record_opening(&recorder, id, &opening);
+    let _ = net_to_ui
+        .send(Message::Handshake {
+            id,
+            status,
+            headers,
+        })
+        .await;
 
-    let (write, mut read) = ws_stream.split();
+    let (mut write, mut read) = ws_stream.split();
+    let (commands_tx, mut commands) = mpsc::channel::<ConnectionCommand>(64);
 
-    let idt = id.clone();
     tokio::spawn(async move {
-        while let Some(result) = read.next().await {
-            match result {
-                Ok(message) => {
-                    let data = message.into_data();
-                    let num_bytes = data.len();
-                    let message_string = String::from_utf8(data).unwrap();
-                    println!("{}", &message_string);
-                    let _ = net_to_ui
-                        .send(Message::Message {
-                            id: idt.to_owned(),
-                            payload: message_string,
-                            num_bytes,
-                        })
-                        .await;
+        let mut close_code = None;
+        let mut closing = false;
+        let mut ping_sent: Option<Instant> = None;
+        let mut script: Option<ScriptHost> = None;
+        let mut tick = tokio::time::interval(SCRIPT_TICK);
+        let mut responders: Vec<AutoResponder> = Vec::new();
+        let mut responses_sent = 0;
+        let mut variables: HashMap<String, String> = HashMap::new();
+        // Responses with a delay are scheduled on their own task and come back through here
+        let (delayed_tx, mut delayed) = mpsc::channel::<String>(64);
+        loop {
+            tokio::select! {
+                result = read.next() => match result {
+                    Some(Ok(tungstenite::Message::Close(frame))) => {
+                        close_code = frame.map(|frame| u16::from(frame.code));
+                    }
+                    Some(Ok(
+                        message @ (tungstenite::Message::Text(_) | tungstenite::Message::Binary(_)),
+                    )) => {
+                        let recorded = match &message {
+                            tungstenite::Message::Text(payload) => RecordedKind::ReceivedText {
+                                payload: payload.clone(),
+                            },
+                            _ => RecordedKind::ReceivedBinary {
+                                data: message.clone().into_data(),
+                            },
+                        };
+                        record(&recorder, id, recorded);
+                        let is_text = message.is_text();
+                        let data = message.into_data();
+                        let num_bytes = data.len();
+                        let message_string = String::from_utf8_lossy(&data).into_owned();
+                        let update = if is_text {
+                            Message::Payload {
+                                id,
+                                payload: message_string.clone(),
+                                num_bytes,
+                            }
+                        } else {
+                            Message::Binary { id, data }
+                        };
+                        let _ = net_to_ui.send(update).await;
+                        for responder in &responders {
+                            let Some(captures) = match_frame(responder, &message_string) else {
+                                continue;
+                            };
+                            // The captures of the pattern take precedence over the connection's
+                            // variables of the same name
+                            let mut variables = variables.clone();
+                            variables.extend(captures);
+                            let context = TemplateContext {
+                                conn_id: id,
+                                counter: responses_sent,
+                                variables: &variables,
+                            };
+                            let payload = render_template(&responder.response, &context);
+                            responses_sent += 1;
+                            if responder.delay_ms == 0 {
+                                send_response(payload, id, &mut write, &net_to_ui, &recorder).await;
+                            } else {
+                                let delayed_tx = delayed_tx.clone();
+                                let delay = Duration::from_millis(responder.delay_ms);
+                                tokio::spawn(async move {
+                                    tokio::time::sleep(delay).await;
+                                    let _ = delayed_tx.send(payload).await;
+                                });
+                            }
+                        }
+                        if let Some(script) = script.as_mut() {
+                            let effects = script.call("on_message", (message_string,));
+                            apply_script_effects(effects, id, &mut write, &net_to_ui, &recorder).await;
+                        }
+                    }
+                    Some(Ok(tungstenite::Message::Pong(_))) => {
+                        let rtt = ping_sent.take().map(|sent| sent.elapsed());
+                        let _ = net_to_ui.send(Message::Pong { id, rtt }).await;
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        eprintln!("Error reading message: {}", e);
+                        break;
+                    }
+                    None => break,
+                },
+                command = commands.recv(), if !closing => match command {
+                    Some(ConnectionCommand::Send(payload)) => {
+                        record(&recorder, id, RecordedKind::SentText { payload: payload.clone() });
+                        let _ = write.send(tungstenite::Message::Text(payload)).await;
+                    }
+                    Some(ConnectionCommand::SendBinary(data)) => {
+                        record(&recorder, id, RecordedKind::SentBinary { data: data.clone() });
+                        let _ = write.send(tungstenite::Message::Binary(data)).await;
+                    }
+                    Some(ConnectionCommand::Ping(payload)) => {
+                        ping_sent = Some(Instant::now());
+                        let _ = write.send(tungstenite::Message::Ping(payload)).await;
+                    }
+                    Some(ConnectionCommand::SetScript(Some(source))) => match ScriptHost::new(&source) {
+                        Ok(mut host) => {
+                            let _ = net_to_ui.send(Message::ScriptStatus { id, attached: true }).await;
+                            let effects = host.call("on_open", ());
+                            apply_script_effects(effects, id, &mut write, &net_to_ui, &recorder).await;
+                            script = Some(host);
+                        }
+                        Err(e) => {
+                            script = None;
+                            let _ = net_to_ui.send(Message::ScriptStatus { id, attached: false }).await;
+                            let effects = vec![ScriptEffect::Fail(format!("Script error: {}", e))];
+                            apply_script_effects(effects, id, &mut write, &net_to_ui, &recorder).await;
+                        }
+                    },
+                    Some(ConnectionCommand::SetScript(None)) => {
+                        script = None;
+                        let _ = net_to_ui.send(Message::ScriptStatus { id, attached: false }).await;
+                    }
+                    Some(ConnectionCommand::SetAutoResponders(rules)) => responders = rules,
+                    Some(ConnectionCommand::SetVariables(values)) => variables = values,
+                    Some(ConnectionCommand::Close(frame)) => {
+                        closing = true;
+                        let recorded = RecordedKind::Close {
+                            code: frame.as_ref().map(|frame| u16::from(frame.code)),
+                            reason: frame.as_ref().map(|frame| frame.reason.to_string()).unwrap_or_default(),
+                        };
+                        record(&recorder, id, recorded);
+                        let _ = write.send(tungstenite::Message::Close(frame)).await;
+                    }
+                    None => {
+                        closing = true;
+                        record(&recorder, id, RecordedKind::Close { code: None, reason: String::new() });
+                        let _ = write.send(tungstenite::Message::Close(None)).await;
+                    }
+                },
+                Some(payload) = delayed.recv(), if !closing => {
+                    send_response(payload, id, &mut write, &net_to_ui, &recorder).await;
                 }
-                Err(e) => {
-                    eprintln!("Error reading message: {}", e);
-                    break;
+                _ = tick.tick(), if script.is_some() => {
+                    if let Some(script) = script.as_mut() {
+                        let effects = script.call("on_tick", ());
+                        apply_script_effects(effects, id, &mut write, &net_to_ui, &recorder).await;
+                    }
                 }
             }
         }
+        if let Some(mut script) = script {
+            let effects = script.call("on_close", ());
+            apply_script_effects(effects, id, &mut write, &net_to_ui, &recorder).await;
+        }
+        record(&recorder, id, RecordedKind::Closed { code: close_code });
+        let _ = net_to_ui
+            .send(Message::Closed {
+                id,
+                code: close_code,
+            })
+            .await;
     });
-    Ok(write)
+    Ok((commands_tx, opening))
+}
+
+async fn apply_script_effects(
+    effects: Vec<ScriptEffect>,
+    id: u8,
+    write: &mut WsSink,
+    net_to_ui: &Sender<Message>,
+    recorder: &SharedRecorder,
+) {
+    for effect in effects {
+        let message = match effect {
+            ScriptEffect::Send(payload) => {
+                send_response(payload, id, write, net_to_ui, recorder).await;
+                continue;
+            }
+            ScriptEffect::Log(line) => Message::ScriptLog {
+                id,
+                line,
+                is_error: false,
+            },
+            ScriptEffect::Fail(line) => Message::ScriptLog {
+                id,
+                line,
+                is_error: true,
+            },
+            ScriptEffect::SetVariable(name, value) => Message::SetVariable { id, name, value },
+        };
+        let _ = net_to_ui.send(message).await;
+    }
+}
+
+/// Sends a frame that did not originate from the UI and reports it so it shows up in the log.
+async fn send_response(
+    payload: String,
+    id: u8,
+    write: &mut WsSink,
+    net_to_ui: &Sender<Message>,
+    recorder: &SharedRecorder,
+) {
+    record(
+        recorder,
+        id,
+        RecordedKind::SentText {
+            payload: payload.clone(),
+        },
+    );
+    let _ = write
+        .send(tungstenite::Message::Text(payload.clone()))
+        .await;
+    let _ = net_to_ui.send(Message::Sent { id, payload }).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::recording::{load_recording, Recorder, ReplaySpeed};
+    use crate::structs::MatchKind;
+
+    /// A server that echoes every text frame.
+    async fn echo_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                    while let Some(Ok(message)) = ws.next().await {
+                        if message.is_text() {
+                            let _ = ws.send(message).await;
+                        }
+                    }
+                });
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn recording_started_on_an_open_connection_replays() {
+        let url = echo_server().await;
+        let (to_network, mut from_ui) = mpsc::channel(64);
+        let (mut to_ui, mut from_network) = mpsc::channel(64);
+        tokio::spawn(async move { network_processor(&mut from_ui, &mut to_ui).await });
+
+        to_network
+            .send(Message::NewClient {
+                id: 1,
+                ip: url.clone(),
+            })
+            .await
+            .unwrap();
+        while !matches!(from_network.recv().await, Some(Message::NewClient { .. })) {}
+
+        let path = std::env::temp_dir().join(format!("session-{}.jsonl", uuid::Uuid::new_v4()));
+        let recorder = Recorder::create(&path).unwrap();
+        to_network
+            .send(Message::StartRecording { recorder })
+            .await
+            .unwrap();
+        to_network
+            .send(Message::Payload {
+                id: 1,
+                payload: String::from("hello"),
+                num_bytes: 0,
+            })
+            .await
+            .unwrap();
+        while !matches!(
+            from_network.recv().await,
+            Some(Message::Payload { ref payload, .. }) if payload == "hello"
+        ) {}
+        to_network.send(Message::StopRecording).await.unwrap();
+
+        let events = load_recording(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(events[0].kind, RecordedKind::Connect { url });
+        assert!(matches!(events[1].kind, RecordedKind::Handshake { .. }));
+        let report = replay_session(events, None, ReplaySpeed::AsFastAsPossible, Vec::new()).await;
+        assert!(report.passed(), "{:?}", report);
+        assert_eq!(report.frames_sent, 1);
+        assert_eq!(report.frames_compared, 1);
+    }
+
+    #[tokio::test]
+    async fn auto_responses_render_connection_variables_and_captures() {
+        let url = echo_server().await;
+        let (to_network, mut from_ui) = mpsc::channel(64);
+        let (mut to_ui, mut from_network) = mpsc::channel(64);
+        tokio::spawn(async move { network_processor(&mut from_ui, &mut to_ui).await });
+
+        to_network
+            .send(Message::NewClient { id: 1, ip: url })
+            .await
+            .unwrap();
+        while !matches!(from_network.recv().await, Some(Message::NewClient { .. })) {}
+
+        let variables = HashMap::from([
+            (String::from("token"), String::from("abc")),
+            (String::from("n"), String::from("shadowed")),
+        ]);
+        to_network
+            .send(Message::SetVariables { id: 1, variables })
+            .await
+            .unwrap();
+        let responder = AutoResponder {
+            kind: MatchKind::Regex,
+            pattern: String::from(r"^ping (?P<n>\d+)$"),
+            response: String::from("pong {{n}} {{token}}"),
+            ..Default::default()
+        };
+        to_network
+            .send(Message::SetAutoResponders {
+                id: 1,
+                responders: vec![responder],
+            })
+            .await
+            .unwrap();
+        to_network
+            .send(Message::Payload {
+                id: 1,
+                payload: String::from("ping 7"),
+                num_bytes: 0,
+            })
+            .await
+            .unwrap();
+
+        let sent = loop {
+            if let Some(Message::Sent { payload, .. }) = from_network.recv().await {
+                break payload;
+            }
+        };
+        assert_eq!(sent, "pong 7 abc");
+    }
+
+    #[tokio::test]
+    async fn hung_handshake_does_not_hold_up_other_connections() {
+        // Accepts connections but never answers their handshake
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hung = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+        let url = echo_server().await;
+        let (to_network, mut from_ui) = mpsc::channel(64);
+        let (mut to_ui, mut from_network) = mpsc::channel(64);
+        tokio::spawn(async move { network_processor(&mut from_ui, &mut to_ui).await });
+
+        for (id, ip) in [(1, hung), (2, url)] {
+            to_network
+                .send(Message::NewClient { id, ip })
+                .await
+                .unwrap();
+        }
+        let connected = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(Message::NewClient { id, .. }) = from_network.recv().await {
+                    break id;
+                }
+            }
+        })
+        .await;
+        assert_eq!(connected.ok(), Some(2));
+    }
 }
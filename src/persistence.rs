@@ -1,39 +1,247 @@
 use anyhow::Result;
-use serde_json::{from_reader, to_string_pretty, Error};
-use std::fs::File;
-use std::io::Write;
+use chrono::Local;
+use serde_json::{from_str, from_value, to_string_pretty, to_value, Value};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use crate::history::MessageLog;
+use crate::migrations::{migrate, version_of, STATE_VERSION};
 use crate::structs::AppState;
 
+const APP_DIR: &str = "rust-socket-sandbox";
+const STATE_FILE: &str = "app_state.json";
+// Values of secret environment variables, kept out of the state file and of exports
+const SECRETS_FILE: &str = "secrets.json";
+// Keeps the state and the data in this directory instead of the platform ones
+const DIR_OVERRIDE_ENV: &str = "SOCKET_SANDBOX_DIR";
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Directory of the saved state, e.g. `$XDG_CONFIG_HOME/rust-socket-sandbox` on Linux.
+pub fn config_dir() -> PathBuf {
+    platform_dir(dirs::config_dir())
+}
+
+/// Directory of generated data such as the message history, e.g. `$XDG_DATA_HOME/...`.
+pub fn data_dir() -> PathBuf {
+    platform_dir(dirs::data_dir())
+}
+
+fn platform_dir(base: Option<PathBuf>) -> PathBuf {
+    match std::env::var_os(DIR_OVERRIDE_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => base.unwrap_or_else(|| PathBuf::from(".")).join(APP_DIR),
+    }
+}
+
+fn state_path() -> PathBuf {
+    config_dir().join(STATE_FILE)
+}
+
+fn secrets_path() -> PathBuf {
+    config_dir().join(SECRETS_FILE)
+}
+
+/// Loads the saved state, migrating it from older versions. A file that cannot be loaded is
+/// kept as a backup next to it instead of being overwritten by the fresh state, and so is the
+/// previous version of a migrated file.
 pub fn get_stored_app() -> AppState {
-    let file = File::open("app_state.json");
-    match file {
-        Ok(file) => {
-            let app_state_result: Result<AppState, Error> = from_reader(file);
-            match app_state_result {
-                Ok(app_state) => app_state,
-                Err(_) => {
-                    let default_state = AppState::default();
-                    if let Err(e) = store_app(&default_state) {
-                        eprintln!("Could not store app state: {:?}", e);
-                    }
-                    default_state
+    let path = state_path();
+    // Earlier versions kept the state in the working directory
+    let legacy = Path::new(STATE_FILE);
+    let source = if !path.exists() && legacy.exists() {
+        legacy
+    } else {
+        path.as_path()
+    };
+
+    let mut app_state = match fs::read_to_string(source) {
+        Ok(text) => match load_state(source, &text) {
+            Ok(app_state) => app_state,
+            Err(e) => {
+                eprintln!("Could not load {}: {:?}", source.display(), e);
+                match back_up(source) {
+                    Ok(backup) => eprintln!("Kept it as {}", backup.display()),
+                    Err(e) => eprintln!("Could not back it up: {:?}", e),
                 }
+                AppState::default()
             }
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => AppState::default(),
+        Err(e) => {
+            eprintln!("Could not read {}: {}", source.display(), e);
+            AppState::default()
         }
-        Err(_) => {
-            let default_state = AppState::default();
-            if let Err(e) = store_app(&default_state) {
-                eprintln!("Could not store app state: {:?}", e);
-            }
-            default_state
+    };
+    load_secrets(&mut app_state);
+    for window in app_state.connections.iter_mut() {
+        window.connection.messages = MessageLog::new(window.id);
+        window.connection.is_connected = false;
+        window.connection.job_running = false;
+        window.apply_codec();
+    }
+    if source == legacy {
+        match store_app(&app_state) {
+            Ok(()) => eprintln!("Copied {} to {}", legacy.display(), path.display()),
+            Err(e) => eprintln!("Could not store app state: {:?}", e),
         }
     }
+    app_state
+}
+
+fn load_state(path: &Path, text: &str) -> Result<AppState> {
+    let mut state: Value = from_str(text)?;
+    let version = version_of(&state);
+    if version > STATE_VERSION {
+        // Keep what a newer build saved, fields unknown to this one would be lost on save
+        eprintln!(
+            "{} was saved by a newer version (state version {}, this build reads {})",
+            path.display(),
+            version,
+            STATE_VERSION
+        );
+        back_up_version(path, version)?;
+    } else if version < STATE_VERSION {
+        back_up_version(path, version)?;
+        migrate(&mut state)?;
+    }
+    Ok(from_value(state)?)
 }
 
 pub fn store_app(app_state: &AppState) -> Result<()> {
-    let app_state_json = to_string_pretty(app_state)?;
-    let mut file = File::create("app_state.json")?;
-    file.write_all(app_state_json.as_bytes())?;
+    let app_state_json = serialize(app_state)?;
+    write_atomically(&state_path(), app_state_json.as_bytes(), false)?;
+    store_secrets(&serialize_secrets(app_state)?)
+}
+
+/// Fills in the secrets of each environment from the secret store.
+fn load_secrets(app_state: &mut AppState) {
+    let path = secrets_path();
+    let mut secrets: BTreeMap<String, BTreeMap<String, String>> = match fs::read_to_string(&path) {
+        Ok(text) => match from_str(&text) {
+            Ok(secrets) => secrets,
+            Err(e) => {
+                eprintln!("Could not load {}: {}", path.display(), e);
+                return;
+            }
+        },
+        Err(e) if e.kind() == ErrorKind::NotFound => return,
+        Err(e) => {
+            eprintln!("Could not read {}: {}", path.display(), e);
+            return;
+        }
+    };
+    for environment in app_state.environments.iter_mut() {
+        if let Some(values) = secrets.remove(&environment.name) {
+            environment.secrets = values;
+        }
+    }
+}
+
+fn serialize_secrets(app_state: &AppState) -> Result<String> {
+    let secrets: BTreeMap<&str, &BTreeMap<String, String>> = app_state
+        .environments
+        .iter()
+        .filter(|environment| !environment.secrets.is_empty())
+        .map(|environment| (environment.name.as_str(), &environment.secrets))
+        .collect();
+    Ok(to_string_pretty(&secrets)?)
+}
+
+fn store_secrets(json: &str) -> Result<()> {
+    let path = secrets_path();
+    // No need for a secret store until there is a secret
+    if json == "{}" && !path.exists() {
+        return Ok(());
+    }
+    write_atomically(&path, json.as_bytes(), true)
+}
+
+fn serialize(app_state: &AppState) -> Result<String> {
+    let mut state = to_value(app_state)?;
+    state["version"] = Value::from(STATE_VERSION);
+    Ok(to_string_pretty(&state)?)
+}
+
+/// Writes to a temporary file next to `path` and renames it over `path`, so a crash while
+/// saving leaves either the old or the new file, never a truncated one. A `private` file is
+/// only readable by the user on Unix.
+fn write_atomically(path: &Path, contents: &[u8], private: bool) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension("json.tmp");
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    let mut file = options.open(&temp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)?;
     Ok(())
 }
+
+fn back_up(path: &Path) -> Result<PathBuf> {
+    let backup = path.with_extension(format!("json.{}.bak", Local::now().format("%Y%m%d-%H%M%S")));
+    fs::rename(path, &backup)?;
+    Ok(backup)
+}
+
+/// Copies the file as last saved in `version`, once, before it is migrated or overwritten.
+fn back_up_version(path: &Path, version: u64) -> Result<()> {
+    let backup = path.with_extension(format!("json.v{}.bak", version));
+    if !backup.exists() {
+        fs::copy(path, &backup)?;
+    }
+    Ok(())
+}
+
+/// Saves the state whenever it changed, checking at most once per `AUTOSAVE_INTERVAL`.
+pub struct Autosave {
+    last_saved: String,
+    last_secrets: String,
+    last_check: Instant,
+}
+
+impl Autosave {
+    pub fn new(app_state: &AppState) -> Self {
+        Self {
+            last_saved: serialize(app_state).unwrap_or_default(),
+            last_secrets: serialize_secrets(app_state).unwrap_or_default(),
+            last_check: Instant::now(),
+        }
+    }
+
+    pub fn tick(&mut self, app_state: &AppState) {
+        if self.last_check.elapsed() >= AUTOSAVE_INTERVAL {
+            self.last_check = Instant::now();
+            self.save_if_changed(app_state);
+        }
+    }
+
+    fn save_if_changed(&mut self, app_state: &AppState) {
+        let result = serialize(app_state).and_then(|json| {
+            if json != self.last_saved {
+                write_atomically(&state_path(), json.as_bytes(), false)?;
+                self.last_saved = json;
+            }
+            let secrets = serialize_secrets(app_state)?;
+            if secrets != self.last_secrets {
+                store_secrets(&secrets)?;
+                self.last_secrets = secrets;
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            eprintln!("Could not store app state: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::time::{sleep_until, timeout_at, Instant};
+use tokio_tungstenite::connect_async;
+use tungstenite::protocol::CloseFrame;
+
+use crate::golden::compare_frames;
+use crate::scenario::WsStream;
+
+// How long replay waits for the responses recorded before a frame before sending it anyway
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Something that happened on a recorded connection, seen from the client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RecordedKind {
+    Connect {
+        url: String,
+    },
+    Handshake {
+        status: u16,
+        headers: Vec<(String, String)>,
+    },
+    SentText {
+        payload: String,
+    },
+    SentBinary {
+        data: Vec<u8>,
+    },
+    ReceivedText {
+        payload: String,
+    },
+    ReceivedBinary {
+        data: Vec<u8>,
+    },
+    /// Close initiated by the client
+    Close {
+        code: Option<u16>,
+        reason: String,
+    },
+    /// The connection ended, `code` is the one sent by the server if any
+    Closed {
+        code: Option<u16>,
+    },
+}
+
+impl RecordedKind {
+    fn is_received(&self) -> bool {
+        matches!(
+            self,
+            Self::ReceivedText { .. } | Self::ReceivedBinary { .. }
+        )
+    }
+
+    fn is_client_action(&self) -> bool {
+        matches!(
+            self,
+            Self::SentText { .. } | Self::SentBinary { .. } | Self::Close { .. }
+        )
+    }
+}
+
+/// One line of a session file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Microseconds since the recording started
+    pub at_us: u64,
+    pub timestamp: DateTime<Utc>,
+    pub conn: u8,
+    #[serde(flatten)]
+    pub kind: RecordedKind,
+}
+
+/// Appends the events of every connection to a session file, one JSON object per line.
+#[derive(Debug)]
+pub struct Recorder {
+    started: std::time::Instant,
+    file: File,
+}
+
+/// The recorder shared by the connection tasks, `None` while not recording.
+pub type SharedRecorder = Arc<Mutex<Option<Recorder>>>;
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self {
+            started: std::time::Instant::now(),
+            file: File::create(path)?,
+        })
+    }
+
+    fn record(&mut self, conn: u8, kind: RecordedKind) -> Result<()> {
+        let event = RecordedEvent {
+            at_us: self.started.elapsed().as_micros() as u64,
+            timestamp: Utc::now(),
+            conn,
+            kind,
+        };
+        let mut line = serde_json::to_string(&event)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// How a connection was opened: its URL and the server's handshake response.
+#[derive(Debug, Clone)]
+pub struct Opening {
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+}
+
+/// Records the opening of a connection if a recording is running. Connections already open
+/// when a recording starts are recorded as opening then, so the session can be replayed.
+pub fn record_opening(recorder: &SharedRecorder, conn: u8, opening: &Opening) {
+    record(
+        recorder,
+        conn,
+        RecordedKind::Connect {
+            url: opening.url.clone(),
+        },
+    );
+    record(
+        recorder,
+        conn,
+        RecordedKind::Handshake {
+            status: opening.status,
+            headers: opening.headers.clone(),
+        },
+    );
+}
+
+/// Records the event if a recording is running.
+pub fn record(recorder: &SharedRecorder, conn: u8, kind: RecordedKind) {
+    if let Some(recorder) = recorder.lock().unwrap().as_mut() {
+        if let Err(e) = recorder.record(conn, kind) {
+            eprintln!("Could not write session recording: {:?}", e);
+        }
+    }
+}
+
+pub fn load_recording(path: &Path) -> Result<Vec<RecordedEvent>> {
+    let file = File::open(path)?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(index, line)| {
+            serde_json::from_str(&line?).map_err(|e| anyhow!("line {}: {}", index + 1, e))
+        })
+        .collect()
+}
+
+/// Pacing of a replay.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    #[default]
+    Original,
+    /// Original timing divided by the factor, 2.0 replays twice as fast
+    Scaled(f64),
+    /// Every frame is sent as soon as the responses recorded before it arrived
+    AsFastAsPossible,
+}
+
+/// A received frame that differs from the recorded one, `None` when one side has no frame.
+/// `changes` lists the changed paths when both frames are JSON.
+#[derive(Debug, Clone)]
+pub struct FrameDiff {
+    pub conn: u8,
+    pub index: usize,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+    pub changes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    pub connections: Vec<u8>,
+    pub frames_sent: usize,
+    pub frames_compared: usize,
+    pub diffs: Vec<FrameDiff>,
+    pub errors: Vec<String>,
+}
+
+impl ReplayReport {
+    pub fn passed(&self) -> bool {
+        self.diffs.is_empty() && self.errors.is_empty()
+    }
+}
+
+/// Replays the client side of every recorded connection concurrently and compares what the
+/// server sends with what it sent during the recording. `target` replaces the recorded URLs
+/// and the `ignore` JSON pointers are left out when comparing JSON frames.
+///
+/// Before a recorded client frame is sent, replay waits until as many frames were received as
+/// had been before it during the recording, so the order of the conversation is kept even when
+/// the server is slower than it was.
+pub async fn replay_session(
+    events: Vec<RecordedEvent>,
+    target: Option<String>,
+    speed: ReplaySpeed,
+    ignore: Vec<String>,
+) -> ReplayReport {
+    let mut connections: BTreeMap<u8, Vec<RecordedEvent>> = BTreeMap::new();
+    for event in events {
+        connections.entry(event.conn).or_default().push(event);
+    }
+
+    let mut report = ReplayReport {
+        connections: connections.keys().copied().collect(),
+        ..Default::default()
+    };
+    let started = Instant::now();
+    let tasks: Vec<_> = connections
+        .into_iter()
+        .map(|(conn, events)| {
+            let (target, ignore) = (target.clone(), ignore.clone());
+            tokio::spawn(async move {
+                (
+                    conn,
+                    replay_connection(conn, &events, target, speed, &ignore, started).await,
+                )
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        match task.await {
+            Ok((_, Ok(connection))) => {
+                report.frames_sent += connection.frames_sent;
+                report.frames_compared += connection.frames_compared;
+                report.diffs.extend(connection.diffs);
+            }
+            Ok((conn, Err(e))) => report.errors.push(format!("#{}: {}", conn, e)),
+            Err(e) => report.errors.push(e.to_string()),
+        }
+    }
+    report
+}
+
+fn scaled(at_us: u64, speed: ReplaySpeed, started: Instant) -> Option<Instant> {
+    match speed {
+        ReplaySpeed::Original => Some(started + Duration::from_micros(at_us)),
+        ReplaySpeed::Scaled(factor) if factor > 0. => {
+            Some(started + Duration::from_micros((at_us as f64 / factor) as u64))
+        }
+        ReplaySpeed::Scaled(_) | ReplaySpeed::AsFastAsPossible => None,
+    }
+}
+
+async fn replay_connection(
+    conn: u8,
+    events: &[RecordedEvent],
+    target: Option<String>,
+    speed: ReplaySpeed,
+    ignore: &[String],
+    started: Instant,
+) -> Result<ReplayReport> {
+    let connect = events
+        .iter()
+        .find_map(|event| match &event.kind {
+            RecordedKind::Connect { url } => Some((event.at_us, url)),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("no connect event recorded"))?;
+    if let Some(due) = scaled(connect.0, speed, started) {
+        sleep_until(due).await;
+    }
+    let url = target.unwrap_or_else(|| connect.1.clone());
+    let (ws_stream, _) = connect_async(url::Url::parse(&url)?).await?;
+    let (mut sink, mut stream) = ws_stream.split();
+
+    let mut received = Vec::new();
+    let mut close_code = None;
+    let mut open = true;
+    let mut report = ReplayReport::default();
+    let mut received_before = 0;
+    for event in events {
+        if event.kind.is_received() {
+            received_before += 1;
+            continue;
+        }
+        if !event.kind.is_client_action() {
+            continue;
+        }
+        if open {
+            let deadline = Instant::now() + RESPONSE_TIMEOUT;
+            open = read_until(
+                &mut stream,
+                &mut received,
+                &mut close_code,
+                deadline,
+                Some(received_before),
+            )
+            .await;
+        }
+        if let (true, Some(due)) = (open, scaled(event.at_us, speed, started)) {
+            open = read_until(&mut stream, &mut received, &mut close_code, due, None).await;
+        }
+        if !open {
+            break;
+        }
+        let message = match &event.kind {
+            RecordedKind::SentText { payload } => tungstenite::Message::Text(payload.clone()),
+            RecordedKind::SentBinary { data } => tungstenite::Message::Binary(data.clone()),
+            RecordedKind::Close { code, reason } => {
+                tungstenite::Message::Close(code.map(|code| CloseFrame {
+                    code: code.into(),
+                    reason: reason.clone().into(),
+                }))
+            }
+            _ => continue,
+        };
+        sink.send(message).await?;
+        report.frames_sent += 1;
+    }
+    if open {
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        let total = events.iter().filter(|e| e.kind.is_received()).count();
+        if read_until(
+            &mut stream,
+            &mut received,
+            &mut close_code,
+            deadline,
+            Some(total),
+        )
+        .await
+        {
+            let _ = sink.send(tungstenite::Message::Close(None)).await;
+        }
+    }
+
+    let expected: Vec<&RecordedKind> = events
+        .iter()
+        .map(|event| &event.kind)
+        .filter(|kind| kind.is_received())
+        .collect();
+    for index in 0..expected.len().max(received.len()) {
+        let (expected, actual) = (expected.get(index).copied(), received.get(index));
+        report.frames_compared += 1;
+        let changes = match (expected, actual) {
+            (
+                Some(RecordedKind::ReceivedText { payload: expected }),
+                Some(RecordedKind::ReceivedText { payload: actual }),
+            ) => compare_frames(expected, actual, ignore),
+            (expected, actual) => (expected != actual).then(Vec::new),
+        };
+        if let Some(changes) = changes {
+            report.diffs.push(FrameDiff {
+                conn,
+                index,
+                expected: expected.map(describe),
+                actual: actual.map(describe),
+                changes,
+            });
+        }
+    }
+
+    // A close by the server is part of its behaviour, one requested by the client is not
+    let client_closed = events
+        .iter()
+        .any(|event| matches!(event.kind, RecordedKind::Close { .. }));
+    let recorded_code = events.iter().find_map(|event| match event.kind {
+        RecordedKind::Closed { code } => Some(code),
+        _ => None,
+    });
+    if let (false, Some(recorded_code)) = (client_closed, recorded_code) {
+        if recorded_code != close_code {
+            report.diffs.push(FrameDiff {
+                conn,
+                index: expected.len(),
+                expected: Some(format!("close code {:?}", recorded_code)),
+                actual: Some(format!("close code {:?}", close_code)),
+                changes: Vec::new(),
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Reads frames until `deadline`, or until `count` frames were received when it is given.
+/// Returns false once the connection is closed.
+async fn read_until(
+    stream: &mut WsStream,
+    received: &mut Vec<RecordedKind>,
+    close_code: &mut Option<u16>,
+    deadline: Instant,
+    count: Option<usize>,
+) -> bool {
+    loop {
+        if count.is_some_and(|count| received.len() >= count) {
+            return true;
+        }
+        let frame = match timeout_at(deadline, stream.next()).await {
+            Err(_) => return true,
+            Ok(None) | Ok(Some(Err(_))) => return false,
+            Ok(Some(Ok(frame))) => frame,
+        };
+        match frame {
+            tungstenite::Message::Text(payload) => {
+                received.push(RecordedKind::ReceivedText { payload })
+            }
+            tungstenite::Message::Binary(data) => {
+                received.push(RecordedKind::ReceivedBinary { data })
+            }
+            tungstenite::Message::Close(frame) => {
+                *close_code = frame.map(|frame| u16::from(frame.code));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn describe(kind: &RecordedKind) -> String {
+    match kind {
+        RecordedKind::ReceivedText { payload } => payload.clone(),
+        RecordedKind::ReceivedBinary { data } => format!("<{} bytes> {:02x?}", data.len(), data),
+        other => format!("{:?}", other),
+    }
+}
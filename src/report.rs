@@ -0,0 +1,144 @@
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+
+/// One reported check of a headless run, e.g. a scenario step on one connection.
+pub struct TestCase {
+    pub suite: String,
+    pub name: String,
+    pub duration: Duration,
+    pub failure: Option<String>,
+    pub skipped: bool,
+    pub output: Vec<String>,
+}
+
+/// Writes the cases as JUnit XML, with one `<testsuite>` per distinct `suite`.
+pub fn write_junit(path: &Path, name: &str, cases: &[TestCase]) -> Result<()> {
+    let mut suites: Vec<&str> = Vec::new();
+    for case in cases {
+        if !suites.contains(&case.suite.as_str()) {
+            suites.push(&case.suite);
+        }
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let (failures, skipped, time) = summarise(cases.iter());
+    writeln!(
+        xml,
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+        escape(name),
+        cases.len(),
+        failures,
+        skipped,
+        time
+    )?;
+    for suite in suites {
+        let suite_cases: Vec<&TestCase> = cases.iter().filter(|c| c.suite == suite).collect();
+        let (failures, skipped, time) = summarise(suite_cases.iter().copied());
+        writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            escape(suite),
+            suite_cases.len(),
+            failures,
+            skipped,
+            time
+        )?;
+        for case in suite_cases {
+            write!(
+                xml,
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                escape(suite),
+                escape(&case.name),
+                case.duration.as_secs_f64()
+            )?;
+            if case.failure.is_none() && !case.skipped && case.output.is_empty() {
+                xml.push_str("/>\n");
+                continue;
+            }
+            xml.push_str(">\n");
+            if let Some(failure) = &case.failure {
+                writeln!(
+                    xml,
+                    "      <failure message=\"{}\">{}</failure>",
+                    escape(failure),
+                    escape(failure)
+                )?;
+            }
+            if case.skipped {
+                xml.push_str("      <skipped/>\n");
+            }
+            if !case.output.is_empty() {
+                writeln!(
+                    xml,
+                    "      <system-out>{}</system-out>",
+                    escape(&case.output.join("\n"))
+                )?;
+            }
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    std::fs::write(path, xml)?;
+    Ok(())
+}
+
+/// Writes the cases as TAP version 13, failures carry a YAML block with the message and output.
+pub fn write_tap(path: &Path, cases: &[TestCase]) -> Result<()> {
+    let mut tap = format!("TAP version 13\n1..{}\n", cases.len());
+    for (index, case) in cases.iter().enumerate() {
+        let status = if case.failure.is_some() {
+            "not ok"
+        } else {
+            "ok"
+        };
+        let description = format!("{}: {}", case.suite, case.name).replace('#', "\\#");
+        write!(tap, "{} {} - {}", status, index + 1, description)?;
+        if case.skipped {
+            tap.push_str(" # SKIP not reached");
+        }
+        tap.push('\n');
+        if let Some(failure) = &case.failure {
+            tap.push_str("  ---\n");
+            writeln!(tap, "  message: {}", serde_json::to_string(failure)?)?;
+            if !case.output.is_empty() {
+                tap.push_str("  frames:\n");
+                for frame in &case.output {
+                    writeln!(tap, "    - {}", serde_json::to_string(frame)?)?;
+                }
+            }
+            tap.push_str("  ...\n");
+        }
+    }
+    std::fs::write(path, tap)?;
+    Ok(())
+}
+
+fn summarise<'a>(cases: impl Iterator<Item = &'a TestCase>) -> (usize, usize, f64) {
+    cases.fold((0, 0, 0.), |(failures, skipped, time), case| {
+        (
+            failures + case.failure.is_some() as usize,
+            skipped + case.skipped as usize,
+            time + case.duration.as_secs_f64(),
+        )
+    })
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control() || matches!(c, '\n' | '\t'))
+        .fold(String::new(), |mut escaped, c| {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&apos;"),
+                c => escaped.push(c),
+            }
+            escaped
+        })
+}
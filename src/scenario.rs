@@ -0,0 +1,530 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+use futures_util::stream::SplitStream;
+use futures_util::{SinkExt, StreamExt};
+use regex::Regex;
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::Sender;
+use tokio::time::{sleep, timeout_at};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use crate::networking::WsSink;
+use crate::structs::Message;
+use crate::templating::{render_template, TemplateContext};
+
+const DEFAULT_EXPECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type WsStream = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
+
+/// A test conversation parsed from a scenario file. One step per line:
+///
+/// ```text
+/// # comments and blank lines are ignored
+/// connect [url]                 connect to url, or to the connection's url
+/// send <template>               send a templated text frame
+/// expect match <text>           wait for a frame equal to text
+/// expect regex <regex>          wait for a matching frame; named groups become variables
+/// expect json <json>            wait for a JSON frame containing this JSON as a subset
+/// timeout <ms>                  timeout of the following expects (default 5000)
+/// wait <ms>                     sleep
+/// set <name> <template>         set a variable
+/// loop <n> ... end              repeat the enclosed steps n times
+/// close                         close the connection
+/// ```
+///
+/// In templates `{{counter}}` is the number of frames sent so far, so steps following a
+/// `send` see the value it was sent with.
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+impl Scenario {
+    /// Line number and source of every step, including the ones nested in loops.
+    pub fn lines(&self) -> Vec<(usize, String)> {
+        fn collect(steps: &[Step], lines: &mut Vec<(usize, String)>) {
+            for step in steps {
+                lines.push((step.line, step.source.clone()));
+                if let Action::Loop(_, body) = &step.action {
+                    collect(body, lines);
+                }
+            }
+        }
+        let mut lines = Vec::new();
+        collect(&self.steps, &mut lines);
+        lines
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub line: usize,
+    pub source: String,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone)]
+pub enum Action {
+    Connect(Option<String>),
+    Send(String),
+    Expect(Matcher),
+    Timeout(Duration),
+    Wait(Duration),
+    Set(String, String),
+    Loop(u32, Vec<Step>),
+    Close,
+}
+
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    Exact(String),
+    Regex(Regex),
+    JsonSubset(Value),
+}
+
+/// Outcome of one executed step. Steps inside a loop report once per iteration, the loop
+/// itself once all its iterations ran or one failed.
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    pub line: usize,
+    pub source: String,
+    pub failure: Option<String>,
+    pub duration: Duration,
+    pub frames: Vec<String>,
+}
+
+pub fn parse_scenario(name: &str, text: &str) -> Result<Scenario> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+    let steps = parse_block(&mut lines, None)?;
+    Ok(Scenario {
+        name: name.to_string(),
+        steps,
+    })
+}
+
+fn parse_block<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    loop_line: Option<usize>,
+) -> Result<Vec<Step>> {
+    let mut steps = Vec::new();
+    while let Some((line, source)) = lines.next() {
+        let (keyword, rest) = source
+            .split_once(char::is_whitespace)
+            .unwrap_or((source, ""));
+        let rest = rest.trim();
+        let action = match keyword {
+            "connect" if rest.is_empty() => Action::Connect(None),
+            "connect" => Action::Connect(Some(rest.to_string())),
+            "send" => Action::Send(rest.to_string()),
+            "expect" => Action::Expect(parse_matcher(rest).map_err(|e| at_line(line, e))?),
+            "timeout" => Action::Timeout(parse_millis(rest).map_err(|e| at_line(line, e))?),
+            "wait" => Action::Wait(parse_millis(rest).map_err(|e| at_line(line, e))?),
+            "set" => match rest.split_once(char::is_whitespace) {
+                Some((name, value)) => Action::Set(name.to_string(), value.trim().to_string()),
+                None => Action::Set(rest.to_string(), String::new()),
+            },
+            "loop" => {
+                let times = rest
+                    .parse()
+                    .map_err(|_| anyhow!("line {}: invalid loop count '{}'", line, rest))?;
+                Action::Loop(times, parse_block(lines, Some(line))?)
+            }
+            "end" if loop_line.is_some() => return Ok(steps),
+            "close" => Action::Close,
+            _ => bail!("line {}: unknown step '{}'", line, keyword),
+        };
+        steps.push(Step {
+            line,
+            source: source.to_string(),
+            action,
+        });
+    }
+    match loop_line {
+        Some(line) => bail!("line {}: loop is missing its 'end'", line),
+        None => Ok(steps),
+    }
+}
+
+fn parse_matcher(text: &str) -> Result<Matcher> {
+    let (kind, arg) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let arg = arg.trim();
+    match kind {
+        "match" => Ok(Matcher::Exact(arg.to_string())),
+        "regex" => Ok(Matcher::Regex(Regex::new(arg)?)),
+        "json" => Ok(Matcher::JsonSubset(serde_json::from_str(arg)?)),
+        _ => bail!(
+            "expected 'match', 'regex' or 'json' after expect, got '{}'",
+            kind
+        ),
+    }
+}
+
+fn parse_millis(text: &str) -> Result<Duration> {
+    let millis: u64 = text
+        .parse()
+        .map_err(|_| anyhow!("invalid milliseconds '{}'", text))?;
+    Ok(Duration::from_millis(millis))
+}
+
+fn at_line(line: usize, error: anyhow::Error) -> anyhow::Error {
+    anyhow!("line {}: {}", line, error)
+}
+
+/// True when every key and array element of `expected` is present in `actual`.
+pub fn json_contains(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => expected.iter().all(|(key, value)| {
+            actual
+                .get(key)
+                .is_some_and(|actual| json_contains(actual, value))
+        }),
+        (Value::Array(actual), Value::Array(expected)) => expected
+            .iter()
+            .all(|value| actual.iter().any(|actual| json_contains(actual, value))),
+        _ => actual == expected,
+    }
+}
+
+struct Runner<'a> {
+    url: &'a str,
+    conn_id: u8,
+    events: &'a Sender<Message>,
+    sink: Option<WsSink>,
+    stream: Option<WsStream>,
+    variables: HashMap<String, String>,
+    counter: u64,
+    expect_timeout: Duration,
+}
+
+/// Runs `scenario` over its own connection, reporting a `Message::ScenarioStep` per executed
+/// step and a final `Message::ScenarioDone`. Stops at the first failing step.
+pub async fn run_scenario(
+    scenario: Scenario,
+    url: String,
+    conn_id: u8,
+    events: Sender<Message>,
+) -> bool {
+    let mut runner = Runner {
+        url: &url,
+        conn_id,
+        events: &events,
+        sink: None,
+        stream: None,
+        variables: HashMap::new(),
+        counter: 0,
+        expect_timeout: DEFAULT_EXPECT_TIMEOUT,
+    };
+    let passed = runner.run_steps(&scenario.steps).await;
+    if let Some(mut sink) = runner.sink.take() {
+        let _ = sink.send(tungstenite::Message::Close(None)).await;
+    }
+    let _ = events
+        .send(Message::ScenarioDone {
+            id: conn_id,
+            passed,
+        })
+        .await;
+    passed
+}
+
+impl Runner<'_> {
+    async fn run_steps(&mut self, steps: &[Step]) -> bool {
+        for step in steps {
+            let started = Instant::now();
+            let mut frames = Vec::new();
+            let failure = match &step.action {
+                // A loop reports once, after its body, and fails with the first failing iteration
+                Action::Loop(times, body) => {
+                    let mut failure = None;
+                    for iteration in 1..=*times {
+                        if !Box::pin(self.run_steps(body)).await {
+                            failure = Some(anyhow!("iteration {} of {} failed", iteration, times));
+                            break;
+                        }
+                    }
+                    failure
+                }
+                action => self.run_step(action, &mut frames).await.err(),
+            };
+            let passed = failure.is_none();
+            let result = StepResult {
+                line: step.line,
+                source: step.source.clone(),
+                failure: failure.map(|e| format!("line {}: {}: {}", step.line, step.source, e)),
+                duration: started.elapsed(),
+                frames,
+            };
+            let _ = self
+                .events
+                .send(Message::ScenarioStep {
+                    id: self.conn_id,
+                    result,
+                })
+                .await;
+            if !passed {
+                return false;
+            }
+        }
+        true
+    }
+
+    async fn run_step(&mut self, action: &Action, frames: &mut Vec<String>) -> Result<()> {
+        match action {
+            Action::Connect(url) => {
+                let url = match url {
+                    Some(url) => self.render(url),
+                    None => self.url.to_string(),
+                };
+                let (ws_stream, _) = connect_async(url::Url::parse(&url)?).await?;
+                let (sink, stream) = ws_stream.split();
+                self.sink = Some(sink);
+                self.stream = Some(stream);
+            }
+            Action::Send(template) => {
+                self.counter += 1;
+                let payload = self.render(template);
+                let sink = self.sink.as_mut().ok_or_else(|| anyhow!("not connected"))?;
+                sink.send(tungstenite::Message::Text(payload)).await?;
+            }
+            Action::Expect(matcher) => self.expect(matcher, frames).await?,
+            Action::Timeout(duration) => self.expect_timeout = *duration,
+            Action::Wait(duration) => sleep(*duration).await,
+            Action::Set(name, template) => {
+                let value = self.render(template);
+                self.variables.insert(name.clone(), value);
+            }
+            Action::Close => {
+                if let Some(mut sink) = self.sink.take() {
+                    sink.send(tungstenite::Message::Close(None)).await?;
+                }
+                self.stream = None;
+            }
+            Action::Loop(..) => unreachable!("loops are run by run_steps"),
+        }
+        Ok(())
+    }
+
+    async fn expect(&mut self, matcher: &Matcher, frames: &mut Vec<String>) -> Result<()> {
+        let exact = match matcher {
+            Matcher::Exact(text) => self.render(text),
+            _ => String::new(),
+        };
+        let stream = self
+            .stream
+            .as_mut()
+            .ok_or_else(|| anyhow!("not connected"))?;
+        let deadline = tokio::time::Instant::now() + self.expect_timeout;
+        loop {
+            let frame = match timeout_at(deadline, stream.next()).await {
+                Err(_) => bail!(
+                    "timed out after {} ms, received {} non-matching frame(s): {:?}",
+                    self.expect_timeout.as_millis(),
+                    frames.len(),
+                    frames
+                ),
+                Ok(None) | Ok(Some(Ok(tungstenite::Message::Close(_)))) => {
+                    bail!("connection closed, received: {:?}", frames)
+                }
+                Ok(Some(Err(e))) => bail!("connection error: {}", e),
+                Ok(Some(Ok(message @ tungstenite::Message::Text(_))))
+                | Ok(Some(Ok(message @ tungstenite::Message::Binary(_)))) => {
+                    String::from_utf8_lossy(&message.into_data()).into_owned()
+                }
+                Ok(Some(Ok(_))) => continue,
+            };
+            frames.push(frame.clone());
+
+            let matched = match matcher {
+                Matcher::Exact(_) => frame == exact,
+                Matcher::Regex(re) => match re.captures(&frame) {
+                    Some(caps) => {
+                        for name in re.capture_names().flatten() {
+                            if let Some(value) = caps.name(name) {
+                                self.variables
+                                    .insert(name.to_string(), value.as_str().to_string());
+                            }
+                        }
+                        true
+                    }
+                    None => false,
+                },
+                Matcher::JsonSubset(expected) => serde_json::from_str::<Value>(&frame)
+                    .is_ok_and(|actual| json_contains(&actual, expected)),
+            };
+            if matched {
+                return Ok(());
+            }
+        }
+    }
+
+    fn render(&self, template: &str) -> String {
+        render_template(
+            template,
+            &TemplateContext {
+                conn_id: self.conn_id,
+                counter: self.counter,
+                variables: &self.variables,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn error(text: &str) -> String {
+        parse_scenario("test", text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_steps_and_skips_comments() {
+        let scenario = parse_scenario(
+            "login",
+            "# log in\n\nconnect\nsend {\"op\": \"login\"}\n  expect json {\"ok\": true}\nclose\n",
+        )
+        .unwrap();
+        assert_eq!(scenario.name, "login");
+        let lines: Vec<usize> = scenario.steps.iter().map(|step| step.line).collect();
+        assert_eq!(lines, vec![3, 4, 5, 6]);
+        assert!(matches!(scenario.steps[0].action, Action::Connect(None)));
+        assert!(
+            matches!(&scenario.steps[1].action, Action::Send(text) if text == r#"{"op": "login"}"#)
+        );
+        assert!(matches!(
+            &scenario.steps[2].action,
+            Action::Expect(Matcher::JsonSubset(value)) if *value == json!({"ok": true})
+        ));
+        assert_eq!(scenario.steps[2].source, r#"expect json {"ok": true}"#);
+        assert!(matches!(scenario.steps[3].action, Action::Close));
+    }
+
+    #[test]
+    fn parses_step_arguments() {
+        let scenario = parse_scenario(
+            "test",
+            "connect ws://localhost:9000\ntimeout 250\nwait 10\nset name ann lee\nset empty\nexpect match pong\nexpect regex ^id=(?P<id>\\d+)$",
+        )
+        .unwrap();
+        let actions: Vec<&Action> = scenario.steps.iter().map(|step| &step.action).collect();
+        assert!(matches!(actions[0], Action::Connect(Some(url)) if url == "ws://localhost:9000"));
+        assert!(matches!(actions[1], Action::Timeout(d) if *d == Duration::from_millis(250)));
+        assert!(matches!(actions[2], Action::Wait(d) if *d == Duration::from_millis(10)));
+        assert!(
+            matches!(actions[3], Action::Set(name, value) if name == "name" && value == "ann lee")
+        );
+        assert!(
+            matches!(actions[4], Action::Set(name, value) if name == "empty" && value.is_empty())
+        );
+        assert!(matches!(actions[5], Action::Expect(Matcher::Exact(text)) if text == "pong"));
+        assert!(matches!(actions[6], Action::Expect(Matcher::Regex(re)) if re.is_match("id=42")));
+    }
+
+    #[test]
+    fn parses_nested_loops() {
+        let scenario = parse_scenario(
+            "test",
+            "connect\nloop 3\n  send a\n  loop 2\n    send b\n  end\n  expect match a\nend\nclose",
+        )
+        .unwrap();
+        assert_eq!(scenario.steps.len(), 3);
+        let Action::Loop(3, outer) = &scenario.steps[1].action else {
+            panic!("expected a loop of 3");
+        };
+        assert_eq!(outer.len(), 3);
+        let Action::Loop(2, inner) = &outer[1].action else {
+            panic!("expected a loop of 2");
+        };
+        assert_eq!(inner.len(), 1);
+        assert_eq!(inner[0].line, 5);
+        assert!(matches!(scenario.steps[2].action, Action::Close));
+
+        let lines: Vec<usize> = scenario.lines().iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![1, 2, 3, 4, 5, 7, 9]);
+    }
+
+    #[test]
+    fn parse_block_stops_at_the_end_of_its_loop() {
+        let mut lines = [(2, "send a"), (3, "end"), (4, "close")].into_iter();
+        let steps = parse_block(&mut lines, Some(1)).unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(lines.next(), Some((4, "close")));
+    }
+
+    #[test]
+    fn rejects_loop_without_end() {
+        assert_eq!(
+            error("connect\nloop 2\n  send a\n  loop 3\n    send b\n  end"),
+            "line 2: loop is missing its 'end'"
+        );
+    }
+
+    #[test]
+    fn rejects_end_outside_a_loop() {
+        assert_eq!(error("send a\nend"), "line 2: unknown step 'end'");
+    }
+
+    #[test]
+    fn rejects_unknown_keywords() {
+        assert_eq!(
+            error("connect\nrecieve pong"),
+            "line 2: unknown step 'recieve'"
+        );
+        assert_eq!(
+            error("expect exact pong"),
+            "line 1: expected 'match', 'regex' or 'json' after expect, got 'exact'"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_numbers() {
+        assert_eq!(error("wait soon"), "line 1: invalid milliseconds 'soon'");
+        assert_eq!(error("timeout -5"), "line 1: invalid milliseconds '-5'");
+        assert_eq!(error("wait"), "line 1: invalid milliseconds ''");
+        assert_eq!(error("loop many\nend"), "line 1: invalid loop count 'many'");
+    }
+
+    #[test]
+    fn rejects_bad_matchers() {
+        assert!(error("expect regex (").starts_with("line 1: "));
+        assert!(error("expect json {oops").starts_with("line 1: "));
+    }
+
+    #[test]
+    fn json_contains_objects_by_subset() {
+        let actual = json!({"type": "ack", "user": {"id": 1, "name": "ann"}, "seq": 4});
+        assert!(json_contains(&actual, &json!({})));
+        assert!(json_contains(&actual, &json!({"user": {"id": 1}})));
+        assert!(!json_contains(&actual, &json!({"user": {"id": 2}})));
+        assert!(!json_contains(&actual, &json!({"missing": null})));
+        assert!(!json_contains(&actual, &json!({"seq": "4"})));
+    }
+
+    #[test]
+    fn json_contains_arrays_by_subset() {
+        let actual = json!({"items": [{"id": 1, "tag": "a"}, {"id": 2, "tag": "b"}, 3]});
+        assert!(json_contains(&actual, &json!({"items": []})));
+        // Expected elements match any element, in any order
+        assert!(json_contains(&actual, &json!({"items": [3, {"id": 2}]})));
+        assert!(json_contains(
+            &actual,
+            &json!({"items": [{"tag": "a"}, {"tag": "a"}]})
+        ));
+        assert!(!json_contains(
+            &actual,
+            &json!({"items": [{"id": 1, "tag": "b"}]})
+        ));
+        assert!(!json_contains(&actual, &json!({"items": [4]})));
+        assert!(!json_contains(&json!([1, 2]), &json!({"0": 1})));
+    }
+}
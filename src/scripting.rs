@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use rhai::{CallFnOptions, Dynamic, Engine, FuncArgs, Scope, AST};
+
+// Wall-clock budget of a single hook call before the script is aborted
+const CPU_TIME_LIMIT: Duration = Duration::from_millis(100);
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Side effects requested by a script, applied by the connection task once the hook returns.
+#[derive(Debug)]
+pub enum ScriptEffect {
+    Send(String),
+    Log(String),
+    Fail(String),
+    SetVariable(String, String),
+}
+
+/// A Rhai script attached to a connection. It may define `on_open()`, `on_message(msg)`,
+/// `on_tick()` and `on_close()`, and call `send(text)`, `log(text)`, `assert(cond, msg)`,
+/// `set_var(name, value)` and `get_var(name)`.
+pub struct ScriptHost {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    effects: Arc<Mutex<Vec<ScriptEffect>>>,
+    started: Arc<Mutex<Instant>>,
+}
+
+impl ScriptHost {
+    pub fn new(source: &str) -> Result<Self> {
+        let effects = Arc::new(Mutex::new(Vec::new()));
+        let variables = Arc::new(Mutex::new(HashMap::<String, String>::new()));
+        let started = Arc::new(Mutex::new(Instant::now()));
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(32);
+        engine.set_max_string_size(1 << 20);
+        engine.set_max_array_size(10_000);
+        engine.set_max_map_size(10_000);
+
+        let call_started = started.clone();
+        engine.on_progress(move |_| {
+            if call_started.lock().unwrap().elapsed() > CPU_TIME_LIMIT {
+                Some(Dynamic::from("CPU time limit exceeded"))
+            } else {
+                None
+            }
+        });
+
+        let sent = effects.clone();
+        engine.register_fn("send", move |text: &str| {
+            sent.lock()
+                .unwrap()
+                .push(ScriptEffect::Send(text.to_string()));
+        });
+        let logged = effects.clone();
+        engine.register_fn("log", move |text: &str| {
+            logged
+                .lock()
+                .unwrap()
+                .push(ScriptEffect::Log(text.to_string()));
+        });
+        let failed = effects.clone();
+        engine.register_fn("assert", move |condition: bool, message: &str| {
+            if !condition {
+                failed
+                    .lock()
+                    .unwrap()
+                    .push(ScriptEffect::Fail(format!("Assertion failed: {}", message)));
+            }
+        });
+        let (set_effects, set_variables) = (effects.clone(), variables.clone());
+        engine.register_fn("set_var", move |name: &str, value: Dynamic| {
+            let value = value.to_string();
+            set_variables
+                .lock()
+                .unwrap()
+                .insert(name.to_string(), value.clone());
+            set_effects
+                .lock()
+                .unwrap()
+                .push(ScriptEffect::SetVariable(name.to_string(), value));
+        });
+        engine.register_fn("get_var", move |name: &str| -> Dynamic {
+            match variables.lock().unwrap().get(name) {
+                Some(value) => value.clone().into(),
+                None => Dynamic::UNIT,
+            }
+        });
+
+        let ast = engine.compile(source).map_err(|e| anyhow!("{}", e))?;
+        let mut host = Self {
+            engine,
+            ast,
+            scope: Scope::new(),
+            effects,
+            started,
+        };
+
+        // Run the top-level statements once, hooks are then called without re-evaluating them
+        *host.started.lock().unwrap() = Instant::now();
+        host.engine
+            .run_ast_with_scope(&mut host.scope, &host.ast)
+            .map_err(|e| anyhow!("{}", e))?;
+        Ok(host)
+    }
+
+    pub fn has_hook(&self, hook: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == hook)
+    }
+
+    /// Calls `hook` if the script defines it and returns the effects it requested. Runtime
+    /// errors, including exceeded limits, are returned as a `ScriptEffect::Fail`.
+    pub fn call(&mut self, hook: &str, args: impl FuncArgs) -> Vec<ScriptEffect> {
+        if !self.has_hook(hook) {
+            return Vec::new();
+        }
+        *self.started.lock().unwrap() = Instant::now();
+        let options = CallFnOptions::new().eval_ast(false).rewind_scope(false);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            hook,
+            args,
+        );
+
+        let mut effects = std::mem::take(&mut *self.effects.lock().unwrap());
+        if let Err(e) = result {
+            effects.push(ScriptEffect::Fail(format!("{} failed: {}", hook, e)));
+        }
+        effects
+    }
+}
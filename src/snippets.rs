@@ -0,0 +1,46 @@
+use anyhow::{bail, Result};
+
+use crate::structs::{Snippet, SnippetKind};
+use crate::utils::parse_hex;
+
+/// Checks a snippet before it is saved, so binary snippets always hold valid hex.
+pub fn validate_snippet(snippet: &Snippet) -> Result<()> {
+    if snippet.name.trim().is_empty() {
+        bail!("Snippet name is empty");
+    }
+    if snippet.kind == SnippetKind::Binary && parse_hex(&snippet.payload).is_none() {
+        bail!("A binary snippet holds hex digits, e.g. de ad be ef");
+    }
+    Ok(())
+}
+
+/// Whether the name, folder or one of the tags of `snippet` contains `filter`, ignoring case.
+pub fn matches_filter(snippet: &Snippet, filter: &str) -> bool {
+    let filter = filter.trim().to_lowercase();
+    filter.is_empty()
+        || snippet.name.to_lowercase().contains(&filter)
+        || snippet.folder.to_lowercase().contains(&filter)
+        || snippet
+            .tags
+            .iter()
+            .any(|tag| tag.to_lowercase().contains(&filter))
+}
+
+/// Folders of the library in display order, the top level first.
+pub fn folders(snippets: &[Snippet]) -> Vec<String> {
+    let mut folders: Vec<String> = snippets.iter().map(|s| s.folder.clone()).collect();
+    folders.sort();
+    folders.dedup();
+    folders
+}
+
+/// Moves the snippet at `from` to the position of the one at `to`, into its folder.
+pub fn move_snippet(snippets: &mut Vec<Snippet>, from: usize, to: usize) {
+    if from == to || from >= snippets.len() || to >= snippets.len() {
+        return;
+    }
+    let folder = snippets[to].folder.clone();
+    let mut snippet = snippets.remove(from);
+    snippet.folder = folder;
+    snippets.insert(to, snippet);
+}
@@ -1,12 +1,29 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tokio::sync::mpsc::Sender;
 
+use crate::autoresponder::Pattern;
+use crate::codec::{load_codec, Codec};
+use crate::diff::Diff;
+use crate::extraction::Extractor;
+use crate::filter::{CompiledFilter, FilterCache};
+use crate::history::{Direction, HistorySearch, LogEntry, MessageLog};
+use crate::recording::{RecordedEvent, Recorder, ReplayReport, ReplaySpeed};
+use crate::scenario::{Scenario, StepResult};
+use crate::utils::parse_json;
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct Connection {
     pub is_connected: bool,
     pub url: String,
-    #[serde(default)] // Don't store messages at they can be huge
-    pub messages: Vec<String>,
+    #[serde(skip)] // Don't store messages as they can be huge, they are logged to disk instead
+    pub messages: MessageLog,
     pub editing_message: String,
     #[serde(default)]
     pub send_bytes: usize,
@@ -15,19 +32,55 @@ pub struct Connection {
     pub send_interval: u32,
     #[serde(default)]
     pub job_running: bool,
+    #[serde(skip)] // Messages the running job sent so far
+    pub job_sent: u64,
+    #[serde(skip)] // When the running job sends its next message, `None` for right away
+    pub job_next: Option<Instant>,
+    #[serde(skip)] // Last payload sent and when, used to blame a disconnect on it
+    pub last_sent: Option<(Instant, String)>,
+    #[serde(skip)]
+    pub send_counter: u64,
+    #[serde(default)]
+    pub extraction_rules: Vec<ExtractionRule>,
+    #[serde(skip)] // Values extracted during this session, available to templates
+    pub variables: HashMap<String, String>,
+    #[serde(default)]
+    pub script: String,
+    #[serde(default)] // The script is sent to every new connection, until it fails to compile
+    pub script_enabled: bool,
+    #[serde(skip)] // The network task compiled the script and runs it on the connection
+    pub script_attached: bool,
+    #[serde(default)]
+    pub auto_responders: Vec<AutoResponder>,
+    #[serde(skip)]
+    pub connected_at: Option<Instant>,
+    #[serde(skip)] // Round trip time of the latest ping
+    pub last_rtt: Option<Duration>,
 }
 
 impl Connection {
-    pub fn new(url: String) -> Self {
+    pub fn new(id: u8, url: String) -> Self {
         Self {
             is_connected: false,
             url,
-            messages: Vec::new(),
+            messages: MessageLog::new(id),
             editing_message: String::new(),
             send_bytes: 0,
             received_bytes: 0,
             send_interval: 1000,
             job_running: false,
+            job_sent: 0,
+            job_next: None,
+            last_sent: None,
+            send_counter: 0,
+            extraction_rules: Vec::new(),
+            variables: HashMap::new(),
+            script: String::new(),
+            script_enabled: false,
+            script_attached: false,
+            auto_responders: Vec::new(),
+            connected_at: None,
+            last_rtt: None,
         }
     }
 }
@@ -35,20 +88,86 @@ impl Connection {
 #[derive(Serialize, Deserialize)]
 pub struct ConnectionWindow {
     pub id: u8,
+    #[serde(default)] // Shown as the title instead of the id when not empty
+    pub name: String,
     pub is_open: bool,
     pub connection: Connection,
     pub send_option: SendOptions,
     pub editing_period: String,
+    #[serde(default)]
+    pub job: JobSettings,
+    #[serde(skip)]
+    pub editing_rule: ExtractionRule,
+    #[serde(skip)]
+    pub rule_error: Option<String>,
+    #[serde(default)]
+    pub scenario_path: String,
+    #[serde(skip)]
+    pub scenario_run: Option<ScenarioRun>,
+    #[serde(skip)]
+    pub scenario_error: Option<String>,
+    #[serde(skip)]
+    pub editing_responder: AutoResponder,
+    #[serde(skip)]
+    pub responder_error: Option<String>,
+    #[serde(skip)] // Older messages being browsed, None while following the latest ones
+    pub history_page: Option<HistoryPage>,
+    #[serde(skip)] // Name under which "Save as Profile" stores the window's settings
+    pub editing_profile: String,
+    #[serde(default)]
+    pub message_view: MessageView,
+    #[serde(skip)]
+    pub filter: MessageFilter,
+    #[serde(skip)]
+    pub filter_cache: FilterCache,
+    #[serde(skip)]
+    pub json_cache: JsonCache,
+    #[serde(skip)] // Entry shown in the inspector, with its index in the history
+    pub inspected: Option<(usize, LogEntry)>,
+    #[serde(skip)] // Up to two entries selected for the diff, in the order they were selected
+    pub compared: Vec<(usize, LogEntry)>,
+    #[serde(skip)]
+    pub diff: Option<Diff>,
+    #[serde(default)]
+    pub codec_settings: CodecSettings,
+    #[serde(skip)] // Loaded from `codec_settings` by `apply_codec`
+    pub codec: Option<Box<dyn Codec>>,
+    #[serde(skip)]
+    pub codec_error: Option<String>,
+    #[serde(default)] // Selects the window with others of its group in the broadcast composer
+    pub group: String,
 }
 
 impl Default for ConnectionWindow {
     fn default() -> Self {
         Self {
             id: 0,
+            name: String::new(),
             is_open: true,
             connection: Connection::default(),
             send_option: SendOptions::Manual,
             editing_period: String::from("1000"),
+            job: JobSettings::default(),
+            editing_rule: ExtractionRule::default(),
+            rule_error: None,
+            scenario_path: String::new(),
+            scenario_run: None,
+            scenario_error: None,
+            editing_responder: AutoResponder::default(),
+            responder_error: None,
+            history_page: None,
+            editing_profile: String::new(),
+            message_view: MessageView::default(),
+            filter: MessageFilter::default(),
+            filter_cache: FilterCache::default(),
+            json_cache: JsonCache::default(),
+            inspected: None,
+            compared: Vec::new(),
+            diff: None,
+            codec_settings: CodecSettings::default(),
+            codec: None,
+            codec_error: None,
+            group: String::new(),
         }
     }
 }
@@ -57,49 +176,762 @@ impl ConnectionWindow {
     pub fn new(id: u8, url: String) -> Self {
         Self {
             id,
+            name: String::new(),
             is_open: true,
-            connection: Connection::new(url),
+            connection: Connection::new(id, url),
             send_option: SendOptions::Manual,
             editing_period: String::from("1000"),
+            job: JobSettings::default(),
+            editing_rule: ExtractionRule::default(),
+            rule_error: None,
+            scenario_path: String::new(),
+            scenario_run: None,
+            scenario_error: None,
+            editing_responder: AutoResponder::default(),
+            responder_error: None,
+            history_page: None,
+            editing_profile: String::new(),
+            message_view: MessageView::default(),
+            filter: MessageFilter::default(),
+            filter_cache: FilterCache::default(),
+            json_cache: JsonCache::default(),
+            inspected: None,
+            compared: Vec::new(),
+            diff: None,
+            codec_settings: CodecSettings::default(),
+            codec: None,
+            codec_error: None,
+            group: String::new(),
+        }
+    }
+}
+
+impl ConnectionWindow {
+    pub fn title(&self) -> String {
+        if self.name.trim().is_empty() {
+            format!("Connection {}", self.id)
+        } else {
+            self.name.clone()
+        }
+    }
+
+    /// Loads the codec of `codec_settings`, keeping the error when it cannot be loaded.
+    pub fn apply_codec(&mut self) {
+        match load_codec(&self.codec_settings) {
+            Ok(codec) => {
+                self.codec = codec;
+                self.codec_error = None;
+            }
+            Err(e) => {
+                self.codec = None;
+                self.codec_error = Some(e.to_string());
+            }
+        }
+    }
+}
+
+/// Format of the binary frames of a connection.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodecSettings {
+    pub kind: CodecKind,
+    /// `.proto` file or descriptor set of `CodecKind::Protobuf`
+    #[serde(default)]
+    pub proto_path: String,
+    /// Full name of the Protobuf message type, e.g. `chat.Envelope`
+    #[serde(default)]
+    pub message_type: String,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CodecKind {
+    /// Binary frames are shown as text when they are UTF-8 and as hex otherwise
+    #[default]
+    None,
+    MessagePack,
+    Cbor,
+    Protobuf,
+}
+
+/// How JSON messages are shown in a connection window, other messages are always shown as is.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MessageView {
+    #[default]
+    Raw,
+    Pretty,
+    Tree,
+}
+
+/// Messages to show, by content, direction and time. Times are local, `HH:MM[:SS]` for today
+/// or `YYYY-MM-DD HH:MM[:SS]`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MessageFilter {
+    pub query: String,
+    pub kind: FilterKind,
+    /// `None` for both directions
+    pub direction: Option<Direction>,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum FilterKind {
+    #[default]
+    Text,
+    Regex,
+    /// Matches JSON messages the query selects at least one node of, e.g. `$[?@.type == 'error']`
+    JsonPath,
+}
+
+/// A message found by the search across all connections.
+#[derive(Debug)]
+pub struct SearchHit {
+    pub conn: u8,
+    /// Position in the connection's message history
+    pub index: usize,
+    pub entry: LogEntry,
+}
+
+/// Settings of the periodic and random send jobs besides the period, as typed in the window.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobSettings {
+    /// Messages to send, -1 for no limit
+    pub quantity: String,
+    pub min_length: String,
+    pub max_length: String,
+    pub letters: bool,
+    pub digits: bool,
+    pub symbols: bool,
+}
+
+impl Default for JobSettings {
+    fn default() -> Self {
+        Self {
+            quantity: String::from("-1"),
+            min_length: String::from("8"),
+            max_length: String::from("32"),
+            letters: true,
+            digits: true,
+            symbols: false,
+        }
+    }
+}
+
+/// Entries `start..start + entries.len()` of a connection's message history.
+pub struct HistoryPage {
+    pub start: usize,
+    pub entries: Vec<LogEntry>,
+}
+
+/// Payloads of the shown entries parsed for the Pretty and Tree views, by index in the
+/// connection's history, so they are not parsed on every frame. `None` when not JSON.
+#[derive(Default)]
+pub struct JsonCache {
+    entries: HashMap<usize, Option<ParsedJson>>,
+}
+
+/// More than the ring and a history page hold, so the shown entries always fit
+const MAX_CACHED_JSON: usize = 2_000;
+
+pub struct ParsedJson {
+    pub value: Value,
+    pub pretty: String,
+}
+
+impl JsonCache {
+    /// Parses the entries that are not cached yet.
+    pub fn update<'a>(&mut self, entries: impl IntoIterator<Item = (usize, &'a LogEntry)>) {
+        for (index, entry) in entries {
+            if self.entries.contains_key(&index) {
+                continue;
+            }
+            if self.entries.len() >= MAX_CACHED_JSON {
+                self.entries.clear();
+            }
+            let parsed = match entry.direction {
+                Direction::Info => None,
+                _ => parse_json(&entry.text).map(|value| ParsedJson {
+                    pretty: serde_json::to_string_pretty(&value).unwrap_or_default(),
+                    value,
+                }),
+            };
+            self.entries.insert(index, parsed);
+        }
+    }
+
+    /// The parsed payload of the entry at `index`, `None` when it is not JSON or not cached.
+    pub fn get(&self, index: usize) -> Option<&ParsedJson> {
+        self.entries.get(&index)?.as_ref()
+    }
+}
+
+/// Progress of a scenario running against a connection window.
+pub struct ScenarioRun {
+    pub name: String,
+    pub lines: Vec<(usize, String)>,
+    pub results: Vec<StepResult>,
+    pub passed: Option<bool>,
+}
+
+/// Stores the value found by `expression` in an incoming frame into `variable`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ExtractionRule {
+    pub variable: String,
+    pub kind: ExtractionKind,
+    pub expression: String,
+    /// Compiled `expression`, kept once the rule was validated or first used. Rules are
+    /// replaced rather than edited once added
+    #[serde(skip)]
+    pub extractor: OnceLock<Option<Extractor>>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ExtractionKind {
+    #[default]
+    JsonPath,
+    Regex,
+}
+
+/// Answers incoming frames matching `pattern` with the templated `response`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct AutoResponder {
+    pub kind: MatchKind,
+    pub pattern: String,
+    pub response: String,
+    pub delay_ms: u64,
+    /// Compiled `pattern`, kept once the responder was validated or first used
+    #[serde(skip)]
+    pub compiled: OnceLock<Option<Pattern>>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MatchKind {
+    #[default]
+    Contains,
+    Regex,
+    Json,
+}
+
+/// Arrangement of the connection windows next to the Connection Manager: panes split side by
+/// side or stacked, each showing one of its connections at a time as tabs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DockLayout {
+    pub panes: Vec<Pane>,
+    #[serde(default)]
+    pub split: Split,
+    /// Width of the Connection Manager side panel
+    pub manager_width: f32,
+}
+
+impl Default for DockLayout {
+    fn default() -> Self {
+        Self {
+            panes: vec![Pane::default()],
+            split: Split::default(),
+            manager_width: 340.,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Pane {
+    /// Ids of the connection windows, in tab order
+    pub tabs: Vec<u8>,
+    pub active: Option<u8>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Split {
+    /// Panes side by side
+    #[default]
+    Horizontal,
+    /// Panes stacked from top to bottom
+    Vertical,
+}
+
+impl DockLayout {
+    /// Drops the tabs of closed windows and empty panes, and opens new windows in the last pane.
+    pub fn sync(&mut self, ids: &[u8]) {
+        for pane in self.panes.iter_mut() {
+            pane.tabs.retain(|id| ids.contains(id));
+        }
+        if self.panes.len() > 1 {
+            self.panes.retain(|pane| !pane.tabs.is_empty());
+        }
+        if self.panes.is_empty() {
+            self.panes.push(Pane::default());
+        }
+        for id in ids {
+            if !self.panes.iter().any(|pane| pane.tabs.contains(id)) {
+                let pane = self.panes.last_mut().unwrap();
+                pane.tabs.push(*id);
+                pane.active = Some(*id);
+            }
         }
+        for pane in self.panes.iter_mut() {
+            if !pane.active.is_some_and(|id| pane.tabs.contains(&id)) {
+                pane.active = pane.tabs.last().copied();
+            }
+        }
+    }
+
+    /// Shows the tab of window `id` in its pane.
+    pub fn focus(&mut self, id: u8) {
+        if let Some(pane) = self.panes.iter_mut().find(|pane| pane.tabs.contains(&id)) {
+            pane.active = Some(id);
+        }
+    }
+
+    /// Moves the tab of window `id` to the pane at `index`, a new pane when it is past the last.
+    pub fn move_tab(&mut self, id: u8, index: usize) {
+        for pane in self.panes.iter_mut() {
+            pane.tabs.retain(|tab| *tab != id);
+        }
+        if index >= self.panes.len() {
+            self.panes.push(Pane::default());
+        }
+        let index = index.min(self.panes.len() - 1);
+        self.panes[index].tabs.push(id);
+        self.panes[index].active = Some(id);
     }
 }
 
+/// Column the connections overview is sorted by.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OverviewColumn {
+    #[default]
+    Id,
+    Status,
+    Url,
+    Uptime,
+    SentBytes,
+    ReceivedBytes,
+    Messages,
+    LastMessage,
+    Rtt,
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct AppState {
     pub connections: Vec<ConnectionWindow>,
     pub editing_ip: String,
     pub in_error: bool,
-    pub windows_open: u8,
+    #[serde(skip)] // Why the last window could not be opened
+    pub open_error: Option<String>,
     pub windows_to_remove: Vec<u8>,
+    #[serde(default)]
+    pub findings: Vec<Finding>,
+    #[serde(default)]
+    pub recording_path: String,
+    #[serde(skip)]
+    pub recording: bool,
+    #[serde(default)]
+    pub replay_path: String,
+    #[serde(default)] // Replaces the recorded URLs when not empty
+    pub replay_target: String,
+    #[serde(skip)]
+    pub replay_speed: ReplaySpeed,
+    #[serde(skip)]
+    pub replay_running: bool,
+    #[serde(skip)]
+    pub replay_report: Option<ReplayReport>,
+    #[serde(skip)]
+    pub recording_error: Option<String>,
+    #[serde(default)]
+    pub goldens: Vec<GoldenRecording>,
+    #[serde(skip)] // Latest report per golden recording, None while it runs
+    pub golden_runs: HashMap<String, Option<ReplayReport>>,
+    #[serde(skip)]
+    pub golden_error: Option<String>,
+    #[serde(default)]
+    pub workspaces: Vec<Workspace>,
+    #[serde(default)] // Name of the workspace whose profiles are shown
+    pub active_workspace: String,
+    #[serde(skip)]
+    pub editing_workspace: String,
+    #[serde(default)] // File a workspace is exported to or imported from
+    pub workspace_path: String,
+    #[serde(skip)]
+    pub workspace_error: Option<String>,
+    #[serde(default)]
+    pub environments: Vec<Environment>,
+    #[serde(default)] // Name of the environment `{{env.NAME}}` templates read from
+    pub active_environment: String,
+    #[serde(skip)]
+    pub editing_environment: String,
+    #[serde(skip)]
+    pub editing_variable: EnvVariable,
+    #[serde(skip)]
+    pub environment_error: Option<String>,
+    #[serde(default)]
+    pub snippets: Vec<Snippet>,
+    #[serde(skip)]
+    pub editing_snippet: Snippet,
+    #[serde(skip)] // Comma separated tags of the snippet being edited
+    pub editing_snippet_tags: String,
+    #[serde(skip)] // Position of the snippet being edited, `None` for a new one
+    pub editing_snippet_index: Option<usize>,
+    #[serde(skip)]
+    pub snippet_filter: String,
+    #[serde(skip)] // Index of the snippet being dragged to a new position
+    pub dragged_snippet: Option<usize>,
+    #[serde(skip)]
+    pub snippet_error: Option<String>,
+    #[serde(skip)]
+    pub search: MessageFilter,
+    #[serde(skip)]
+    pub search_results: Vec<SearchHit>,
+    #[serde(skip)]
+    pub search_error: Option<String>,
+    #[serde(skip)]
+    pub search_running: bool,
+    #[serde(skip)] // The filter the results were found with, to highlight them
+    pub search_filter: Option<Arc<CompiledFilter>>,
+    #[serde(default)]
+    pub broadcast_message: String,
+    #[serde(skip)] // Ids of the windows a broadcast is sent to
+    pub broadcast_targets: BTreeSet<u8>,
+    #[serde(default)]
+    pub show_overview: bool,
+    #[serde(skip)]
+    pub overview_sort: OverviewColumn,
+    #[serde(skip)]
+    pub overview_descending: bool,
+    #[serde(skip)] // Ids of the windows bulk actions apply to
+    pub overview_selection: BTreeSet<u8>,
+    #[serde(default)]
+    pub layout: DockLayout,
 }
 
 impl AppState {
-    pub fn insert_new_window(&mut self, url: String) -> u8 {
-        self.windows_open += 1;
-        self.connections
-            .push(ConnectionWindow::new(self.windows_open, url));
-        return self.windows_open;
+    /// Opens a window with the lowest id not in use, returns its id.
+    pub fn insert_new_window(&mut self, url: String) -> Result<u8> {
+        let Some(id) = (1..=u8::MAX).find(|id| self.connections.iter().all(|w| w.id != *id)) else {
+            bail!(
+                "All {} connection windows are open, close one first",
+                u8::MAX
+            );
+        };
+        self.connections.push(ConnectionWindow::new(id, url));
+        Ok(id)
+    }
+
+    /// Opens a window with the settings of `profile`, returns its id.
+    pub fn open_profile(&mut self, profile: &ConnectionProfile) -> Result<u8> {
+        let id = self.insert_new_window(profile.url.clone())?;
+        let window = self.connections.last_mut().unwrap();
+        window.send_option = profile.send_option;
+        window.editing_period = profile.send_interval.to_string();
+        window.connection.send_interval = profile.send_interval;
+        window.connection.script = profile.script.clone();
+        window.connection.script_enabled = !profile.script.is_empty();
+        window.connection.extraction_rules = profile.extraction_rules.clone();
+        window.connection.auto_responders = profile.auto_responders.clone();
+        window.codec_settings = profile.codec.clone();
+        window.apply_codec();
+        window.name = profile.name.clone();
+        Ok(id)
+    }
+
+    pub fn workspace(&self) -> Option<&Workspace> {
+        self.workspaces
+            .iter()
+            .find(|workspace| workspace.name == self.active_workspace)
+    }
+
+    /// The active workspace, created when it does not exist yet.
+    pub fn workspace_mut(&mut self) -> &mut Workspace {
+        if self.active_workspace.is_empty() {
+            self.active_workspace = String::from(DEFAULT_WORKSPACE);
+        }
+        let index = match self
+            .workspaces
+            .iter()
+            .position(|workspace| workspace.name == self.active_workspace)
+        {
+            Some(index) => index,
+            None => {
+                self.workspaces.push(Workspace {
+                    name: self.active_workspace.clone(),
+                    profiles: Vec::new(),
+                });
+                self.workspaces.len() - 1
+            }
+        };
+        &mut self.workspaces[index]
+    }
+
+    pub fn environment(&self) -> Option<&Environment> {
+        self.environments
+            .iter()
+            .find(|environment| environment.name == self.active_environment)
+    }
+
+    /// The active environment, created when it does not exist yet.
+    pub fn environment_mut(&mut self) -> &mut Environment {
+        if self.active_environment.is_empty() {
+            self.active_environment = String::from(DEFAULT_ENVIRONMENT);
+        }
+        let index = match self
+            .environments
+            .iter()
+            .position(|environment| environment.name == self.active_environment)
+        {
+            Some(index) => index,
+            None => {
+                self.environments.push(Environment {
+                    name: self.active_environment.clone(),
+                    ..Default::default()
+                });
+                self.environments.len() - 1
+            }
+        };
+        &mut self.environments[index]
+    }
+
+    /// Finds a profile by `workspace/profile`, or by name in the active workspace.
+    pub fn find_profile(&self, name: &str) -> Option<&ConnectionProfile> {
+        let (workspace, profile) = match name.split_once('/') {
+            Some((workspace, profile)) => (
+                self.workspaces.iter().find(|w| w.name == workspace)?,
+                profile,
+            ),
+            None => (self.workspace()?, name),
+        };
+        workspace.profiles.iter().find(|p| p.name == profile)
+    }
+}
+
+pub const DEFAULT_WORKSPACE: &str = "default";
+pub const DEFAULT_ENVIRONMENT: &str = "default";
+
+/// Named variables such as a base URL or tokens, referenced as `{{env.NAME}}` from URLs and
+/// templates.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Environment {
+    pub name: String,
+    pub variables: BTreeMap<String, String>,
+    #[serde(skip)] // Kept in the secret store, see `persistence::load_secrets`
+    pub secrets: BTreeMap<String, String>,
+}
+
+/// A variable being added to an environment.
+#[derive(Debug, Clone, Default)]
+pub struct EnvVariable {
+    pub name: String,
+    pub value: String,
+    pub secret: bool,
+}
+
+/// A named set of connection profiles, e.g. one per environment such as "staging" or "local".
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Workspace {
+    pub name: String,
+    pub profiles: Vec<ConnectionProfile>,
+}
+
+/// The saved settings of a connection, opened as a new connection window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionProfile {
+    pub name: String,
+    pub url: String,
+    pub send_option: SendOptions,
+    /// Milliseconds between periodic sends
+    pub send_interval: u32,
+    #[serde(default)]
+    pub script: String,
+    #[serde(default)]
+    pub extraction_rules: Vec<ExtractionRule>,
+    #[serde(default)]
+    pub auto_responders: Vec<AutoResponder>,
+    #[serde(default)]
+    pub codec: CodecSettings,
+}
+
+impl ConnectionProfile {
+    pub fn from_window(name: String, window: &ConnectionWindow) -> Self {
+        Self {
+            name,
+            url: window.connection.url.clone(),
+            send_option: window.send_option,
+            send_interval: window
+                .editing_period
+                .parse()
+                .unwrap_or(window.connection.send_interval),
+            script: window.connection.script.clone(),
+            extraction_rules: window.connection.extraction_rules.clone(),
+            auto_responders: window.connection.auto_responders.clone(),
+            codec: window.codec_settings.clone(),
+        }
     }
 }
 
+/// A saved payload, inserted into or sent from any connection window.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snippet {
+    pub name: String,
+    /// Folder shown in the library, empty for the top level
+    #[serde(default)]
+    pub folder: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub kind: SnippetKind,
+    /// A template for text snippets, hex digits for binary ones
+    pub payload: String,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SnippetKind {
+    #[default]
+    Text,
+    Binary,
+}
+
+/// A recorded session whose server responses are the expected ones, replayed to catch protocol
+/// regressions between server builds.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GoldenRecording {
+    pub name: String,
+    pub path: String,
+    /// JSON pointers of volatile fields, e.g. timestamps and ids, left out of the comparison
+    pub ignore: Vec<String>,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_passed: Option<bool>,
+    #[serde(skip)]
+    pub editing_ignore: String,
+}
+
+/// An input that made the server drop the connection, as confirmed by crash triage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    pub url: String,
+    pub input: String,
+    pub minimised_input: String,
+    pub close_code: Option<u16>,
+    pub reproducible: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
 #[derive(Debug)]
 pub enum Message {
     NewClient {
         id: u8,
         ip: String,
     },
-    Message {
+    Payload {
         id: u8,
         payload: String,
         num_bytes: usize,
     },
+    Binary {
+        id: u8,
+        data: Vec<u8>,
+    },
+    Ping {
+        id: u8,
+        payload: Vec<u8>,
+    },
+    Pong {
+        id: u8,
+        rtt: Option<Duration>,
+    },
+    Handshake {
+        id: u8,
+        status: u16,
+        headers: Vec<(String, String)>,
+    },
     Close {
         id: u8,
+        code: Option<u16>,
+        reason: String,
+    },
+    Closed {
+        id: u8,
+        code: Option<u16>,
+    },
+    Triage {
+        id: u8,
+        ip: String,
+        input: String,
+    },
+    TriageDone {
+        id: u8,
+        finding: Finding,
+    },
+    /// Searches the histories for the search across all connections
+    Search {
+        filter: Arc<CompiledFilter>,
+        histories: Vec<(u8, HistorySearch)>,
+    },
+    SearchDone {
+        results: Vec<SearchHit>,
+        error: Option<String>,
+    },
+    RunScenario {
+        id: u8,
+        ip: String,
+        scenario: Scenario,
+    },
+    ScenarioStep {
+        id: u8,
+        result: StepResult,
+    },
+    ScenarioDone {
+        id: u8,
+        passed: bool,
+    },
+    SetScript {
+        id: u8,
+        source: Option<String>,
+    },
+    SetAutoResponders {
+        id: u8,
+        responders: Vec<AutoResponder>,
+    },
+    /// The connection's variables, which the auto-responders' responses are rendered with.
+    SetVariables {
+        id: u8,
+        variables: HashMap<String, String>,
+    },
+    Sent {
+        id: u8,
+        payload: String,
+    },
+    ScriptLog {
+        id: u8,
+        line: String,
+        is_error: bool,
+    },
+    /// Sent after `SetScript`, `attached` is false when the script was detached or does not
+    /// compile
+    ScriptStatus {
+        id: u8,
+        attached: bool,
+    },
+    SetVariable {
+        id: u8,
+        name: String,
+        value: String,
+    },
+    StartRecording {
+        recorder: Recorder,
+    },
+    StopRecording,
+    /// Replays a recording, `golden` names the golden recording being checked if any
+    Replay {
+        golden: Option<String>,
+        events: Vec<RecordedEvent>,
+        target: Option<String>,
+        speed: ReplaySpeed,
+        ignore: Vec<String>,
+    },
+    ReplayDone {
+        golden: Option<String>,
+        report: ReplayReport,
     },
 }
 
-#[derive(PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum SendOptions {
     Periodically,
     Random,
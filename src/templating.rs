@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+use regex::{Captures, Regex};
+
+use crate::environment;
+
+/// Per-send values available to template expressions.
+pub struct TemplateContext<'a> {
+    pub conn_id: u8,
+    pub counter: u64,
+    pub variables: &'a HashMap<String, String>,
+}
+
+/// Expands `{{...}}` expressions in `template`. Supported expressions are `uuid`,
+/// `timestamp_ms`, `counter`, `random_int <min> <max>`, `conn_id`, `env.<NAME>` (a variable of
+/// the active environment or of the process) and the name of any connection variable.
+/// Unknown or unresolvable expressions are left untouched so the mistake is visible.
+pub fn render_template(template: &str, context: &TemplateContext) -> String {
+    static EXPRESSION: OnceLock<Regex> = OnceLock::new();
+    let re = EXPRESSION.get_or_init(|| Regex::new(r"\{\{\s*([^{}]*?)\s*\}\}").unwrap());
+    re.replace_all(template, |caps: &Captures| {
+        evaluate(&caps[1], context).unwrap_or_else(|| caps[0].to_string())
+    })
+    .into_owned()
+}
+
+fn evaluate(expression: &str, context: &TemplateContext) -> Option<String> {
+    let mut parts = expression.split_whitespace();
+    let name = parts.next()?;
+    let args: Vec<&str> = parts.collect();
+
+    match (name, args.as_slice()) {
+        ("uuid", []) => Some(uuid::Uuid::new_v4().to_string()),
+        ("timestamp_ms", []) => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+            Some(now.as_millis().to_string())
+        }
+        ("counter", []) => Some(context.counter.to_string()),
+        ("conn_id", []) => Some(context.conn_id.to_string()),
+        ("random_int", [min, max]) => {
+            let min: i64 = min.parse().ok()?;
+            let max: i64 = max.parse().ok()?;
+            if min > max {
+                return None;
+            }
+            Some(rand::thread_rng().gen_range(min..=max).to_string())
+        }
+        (name, []) => match name.strip_prefix("env.") {
+            Some(env_name) => environment::lookup(env_name),
+            None => context.variables.get(name).cloned(),
+        },
+        _ => None,
+    }
+}
+
+/// Expands the templates of a URL, e.g. `{{env.BASE_URL}}/chat`, before connecting to it.
+pub fn render_url(url: &str) -> String {
+    render_template(
+        url,
+        &TemplateContext {
+            conn_id: 0,
+            counter: 0,
+            variables: &HashMap::new(),
+        },
+    )
+}
@@ -0,0 +1,247 @@
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use futures_util::{SinkExt, StreamExt};
+use tokio::time::timeout;
+use tokio_tungstenite::connect_async;
+
+use crate::environment::mask;
+use crate::structs::Finding;
+
+// How long a probe connection has to be dropped by the server after the input is sent
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+// Upper bound of probe connections spent minimising a single input
+const MAX_PROBES: usize = 200;
+
+/// Whether a close with `code` looks like the server failed rather than chose to close: no
+/// close frame, 1006 (abnormal closure) or 1011 (internal error).
+pub fn is_crash(code: Option<u16>) -> bool {
+    matches!(code, None | Some(1006) | Some(1011))
+}
+
+/// Reconnects to `url`, re-sends `input` to check that it reliably kills the connection and,
+/// if it does, reduces it to the smallest payload that still does. The active environment's
+/// secrets are masked in the finding, which is saved with the app state.
+pub async fn triage_crash(url: String, input: String) -> Result<Finding> {
+    let close_code = probe(&url, &input).await?;
+    let reproducible = crashed(close_code);
+    let minimised_input = if reproducible {
+        minimise(&url, &input).await
+    } else {
+        input.clone()
+    };
+
+    Ok(Finding {
+        url: mask(&url),
+        input: mask(&input),
+        minimised_input: mask(&minimised_input),
+        close_code: close_code.flatten(),
+        reproducible,
+        timestamp: Utc::now(),
+    })
+}
+
+/// Whether a probe ended with the server dropping the connection the way `is_crash` describes,
+/// a clean close does not count.
+fn crashed(close_code: Option<Option<u16>>) -> bool {
+    matches!(close_code, Some(code) if is_crash(code))
+}
+
+/// Sends `payload` over a fresh connection. Returns `Some(close code)` when the server drops
+/// the connection within `PROBE_TIMEOUT` and `None` when it survives.
+async fn probe(url: &str, payload: &str) -> Result<Option<Option<u16>>> {
+    let (ws_stream, _) = connect_async(url::Url::parse(url)?).await?;
+    let (mut write, mut read) = ws_stream.split();
+    write
+        .send(tungstenite::Message::Text(payload.to_string()))
+        .await?;
+
+    let mut close_code = None;
+    let dropped = timeout(PROBE_TIMEOUT, async {
+        while let Some(result) = read.next().await {
+            match result {
+                Ok(tungstenite::Message::Close(frame)) => {
+                    close_code = frame.map(|frame| u16::from(frame.code));
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+    })
+    .await
+    .is_ok();
+
+    if dropped {
+        Ok(Some(close_code))
+    } else {
+        let _ = write.send(tungstenite::Message::Close(None)).await;
+        Ok(None)
+    }
+}
+
+/// The smallest part of `input` that still crashes the server at `url`.
+async fn minimise(url: &str, input: &str) -> String {
+    ddmin(input, MAX_PROBES, |payload| async move {
+        Ok(crashed(probe(url, &payload).await?))
+    })
+    .await
+}
+
+/// Delta debugging (ddmin) over the characters of `input`, looking for the smallest input
+/// `fails` holds for. Stops early once `max_probes` is spent or `fails` errs, returning the
+/// smallest failing input found so far.
+async fn ddmin<F, Fut>(input: &str, max_probes: usize, mut fails: F) -> String
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    let mut chars: Vec<char> = input.chars().collect();
+    let mut granularity = 2;
+    let mut probes = 0;
+
+    'outer: while chars.len() >= 2 {
+        let chunk_len = chars.len().div_ceil(granularity);
+        let chunks: Vec<(usize, usize)> = (0..chars.len())
+            .step_by(chunk_len)
+            .map(|start| (start, (start + chunk_len).min(chars.len())))
+            .collect();
+
+        // Try each chunk on its own first, then the input with each chunk removed
+        let subsets = chunks
+            .iter()
+            .map(|&(start, end)| chars[start..end].to_vec());
+        let complements = chunks
+            .iter()
+            .map(|&(start, end)| [&chars[..start], &chars[end..]].concat());
+        for (is_subset, candidate) in subsets
+            .map(|c| (true, c))
+            .chain(complements.map(|c| (false, c)))
+            .collect::<Vec<_>>()
+        {
+            if candidate.is_empty() || candidate.len() == chars.len() {
+                continue;
+            }
+            if probes >= max_probes {
+                break 'outer;
+            }
+            probes += 1;
+            let payload: String = candidate.iter().collect();
+            match fails(payload).await {
+                Ok(true) => {
+                    chars = candidate;
+                    granularity = if is_subset {
+                        2
+                    } else {
+                        (granularity - 1).max(2)
+                    };
+                    continue 'outer;
+                }
+                Ok(false) => {}
+                Err(_) => break 'outer,
+            }
+        }
+
+        if granularity >= chars.len() {
+            break;
+        }
+        granularity = (granularity * 2).min(chars.len());
+    }
+
+    chars.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn ddmin_finds_the_failing_substring() {
+        let minimised = ddmin("aaaa{\"crash\":1}bbbb", MAX_PROBES, |payload| async move {
+            Ok(payload.contains("crash"))
+        })
+        .await;
+        assert_eq!(minimised, "crash");
+    }
+
+    #[tokio::test]
+    async fn ddmin_keeps_characters_that_fail_only_together() {
+        let minimised = ddmin("x1yyyyyyyyy2z", MAX_PROBES, |payload| async move {
+            Ok(payload.contains('1') && payload.contains('2'))
+        })
+        .await;
+        assert_eq!(minimised, "12");
+    }
+
+    #[tokio::test]
+    async fn ddmin_stops_after_the_probe_budget() {
+        let mut probes = 0;
+        let minimised = ddmin("0123456789", 3, |payload| {
+            probes += 1;
+            async move { Ok(payload.contains('5')) }
+        })
+        .await;
+        assert_eq!(probes, 3);
+        assert!(minimised.contains('5'));
+        assert!(minimised.len() < 10);
+    }
+
+    #[tokio::test]
+    async fn ddmin_returns_the_input_when_probes_fail() {
+        let minimised = ddmin("payload", MAX_PROBES, |_| async {
+            anyhow::bail!("connection refused")
+        })
+        .await;
+        assert_eq!(minimised, "payload");
+    }
+
+    /// A server that closes every connection with `code` once it gets a message.
+    async fn closing_server(code: u16) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                    if ws.next().await.is_some() {
+                        let frame = tungstenite::protocol::CloseFrame {
+                            code: code.into(),
+                            reason: "".into(),
+                        };
+                        let _ = ws.close(Some(frame)).await;
+                        while ws.next().await.is_some() {}
+                    }
+                });
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn clean_close_is_not_reproducible() {
+        let url = closing_server(1000).await;
+        let finding = triage_crash(url, String::from("{\"a\":1}")).await.unwrap();
+        assert!(!finding.reproducible);
+        assert_eq!(finding.close_code, Some(1000));
+        assert_eq!(finding.minimised_input, "{\"a\":1}");
+    }
+
+    #[tokio::test]
+    async fn internal_error_close_is_reproducible() {
+        let url = closing_server(1011).await;
+        let finding = triage_crash(url, String::from("xyz")).await.unwrap();
+        assert!(finding.reproducible);
+        assert_eq!(finding.close_code, Some(1011));
+        assert_eq!(finding.minimised_input.chars().count(), 1);
+    }
+
+    #[test]
+    fn only_abnormal_closes_are_crashes() {
+        assert!(is_crash(None));
+        assert!(is_crash(Some(1006)));
+        assert!(is_crash(Some(1011)));
+        assert!(!is_crash(Some(1000)));
+        assert!(!is_crash(Some(1001)));
+    }
+}
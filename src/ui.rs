@@ -1,10 +1,43 @@
-use crate::structs::{AppState, Message, SendOptions, WindowAction};
-use crate::utils::is_valid_websocket_ip;
+use crate::autoresponder::validate_responder;
+use crate::diff::{diff, ChangeKind, Diff, DiffLine};
+use crate::environment::{self, MASK};
+use crate::extraction::{extract, validate_rule};
+use crate::filter::CompiledFilter;
+use crate::golden::validate_pointer;
+use crate::history::{Direction, LogEntry, Opcode};
+use crate::persistence::{store_app, Autosave};
+use crate::recording::{load_recording, Recorder, ReplayReport, ReplaySpeed};
+use crate::scenario::parse_scenario;
+use crate::snippets::{folders, matches_filter, move_snippet, validate_snippet};
+use crate::structs::{
+    AppState, CodecKind, Connection, ConnectionProfile, ConnectionWindow, ExtractionKind,
+    FilterKind, GoldenRecording, HistoryPage, MatchKind, Message, MessageFilter, MessageView,
+    OverviewColumn, ParsedJson, ScenarioRun, SendOptions, Snippet, SnippetKind, Split,
+    WindowAction,
+};
+use crate::templating::{render_template, render_url, TemplateContext};
+use crate::triage::is_crash;
+use crate::utils::{
+    hex_dump, is_valid_websocket_ip, json_path_key, parse_hex, parse_json, random_text,
+};
+use crate::workspace::{add_workspace, export_workspace, import_workspace};
 use eframe::egui;
-use egui::{CollapsingHeader, Context, Resize};
+use egui::text::LayoutJob;
+use egui::{CollapsingHeader, Context};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::{Receiver, Sender};
 
+// An abnormal disconnect this soon after a send is blamed on the sent payload and triaged
+const CRASH_WINDOW: Duration = Duration::from_secs(1);
+const TEMPLATE_HINT: &str = "Expanded on send: {{uuid}}, {{timestamp_ms}}, {{counter}}, \
+    {{random_int 1 100}}, {{conn_id}}, {{env.NAME}}";
+// Entries per page when browsing history older than the in-memory messages
+const HISTORY_PAGE_SIZE: usize = 200;
+
 pub struct UI {
     pub app_state: Arc<Mutex<AppState>>,
     pub network_to_ui: Arc<Mutex<Receiver<Message>>>,
@@ -35,102 +68,367 @@ impl UI {
         let ntui = Arc::clone(&self.network_to_ui);
 
         let mut should_create_connection = false;
+        let mut autosave = Autosave::new(&app_state.lock().unwrap());
 
-        eframe::run_simple_native("Rust Socket Sandbox", options, move |ctx, _frame| {
-            egui::CentralPanel::default().show(ctx, |_ui| {
-                egui::Window::new("Connection Manager").show(ctx, |ui| {
-                    let mut state = app_state.lock().unwrap();
-                    if state.in_error {
-                        ui.horizontal(|ui| {
-                            ui.centered_and_justified(|ui| {
-                                ui.colored_label(egui::Color32::RED, "Invalid IP Address");
+        let result =
+            eframe::run_simple_native("Rust Socket Sandbox", options, move |ctx, _frame| {
+                let manager_width = app_state.lock().unwrap().layout.manager_width;
+                let manager = egui::SidePanel::left("connection_manager")
+                    .resizable(true)
+                    .default_width(manager_width)
+                    .show(ctx, |ui| {
+                        ui.heading("Connection Manager");
+                        let mut state = app_state.lock().unwrap();
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            if state.in_error {
+                                ui.horizontal(|ui| {
+                                    ui.centered_and_justified(|ui| {
+                                        ui.colored_label(egui::Color32::RED, "Invalid IP Address");
+                                    });
+                                });
+                            }
+                            if let Some(error) = &state.open_error {
+                                ui.colored_label(egui::Color32::RED, error);
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label("Ip Address:");
+                                ui.text_edit_singleline(&mut state.editing_ip);
+                                if ui.button("Create Connection").clicked() {
+                                    if is_valid_websocket_ip(&render_url(&state.editing_ip)) {
+                                        state.in_error = false;
+                                        should_create_connection = !state.editing_ip.is_empty();
+                                    } else {
+                                        state.in_error = true;
+                                    }
+                                }
                             });
+                            ui.horizontal(|ui| {
+                                ui.toggle_value(&mut state.show_overview, "Connections Overview");
+                                ui.separator();
+                                ui.label("Panes:");
+                                ui.radio_value(
+                                    &mut state.layout.split,
+                                    Split::Horizontal,
+                                    "Side by side",
+                                );
+                                ui.radio_value(&mut state.layout.split, Split::Vertical, "Stacked");
+                            });
+                            render_workspace(ui, &mut state, &utnw);
+                            render_environment(ui, &mut state);
+                            render_snippets(ui, &mut state);
+                            render_broadcast(ui, &mut state, &utnw);
+                            render_search(ui, &mut state, &utnw);
+                            render_findings(ui, &mut state);
+                            render_recording(ui, &mut state, &utnw);
+                            render_goldens(ui, &mut state, &utnw);
                         });
-                    }
-                    ui.horizontal(|ui| {
-                        ui.label("Ip Address:");
-                        ui.text_edit_singleline(&mut state.editing_ip);
-                        if ui.button("Create Connection").clicked() {
-                            if is_valid_websocket_ip(&state.editing_ip) {
-                                state.in_error = false;
-                                should_create_connection = true && !state.editing_ip.is_empty();
-                            } else {
-                                state.in_error = true;
-                            }
-                        }
                     });
-                });
-            });
+                app_state.lock().unwrap().layout.manager_width = manager.response.rect.width();
 
-            let mut ntui_lock = ntui.lock().unwrap();
-            while let Ok(message) = ntui_lock.try_recv() {
-                match message {
-                    Message::NewClient { id, ip } => todo!(),
-                    Message::Message {
-                        id,
-                        payload,
-                        num_bytes,
-                    } => {
-                        let mut state = app_state.lock().unwrap();
-                        for window in state.connections.iter_mut() {
-                            if window.id == id {
-                                window.connection.messages.push(payload.to_owned());
-                                window.connection.received_bytes += num_bytes;
-                                break;
+                render_overview(ctx, &mut app_state.lock().unwrap(), &utnw);
+
+                let mut ntui_lock = ntui.lock().unwrap();
+                while let Ok(message) = ntui_lock.try_recv() {
+                    match message {
+                        Message::Payload {
+                            id,
+                            payload,
+                            num_bytes,
+                        } => {
+                            let mut state = app_state.lock().unwrap();
+                            for window in state.connections.iter_mut() {
+                                if window.id == id {
+                                    let connection = &mut window.connection;
+                                    let extracted = extract(&connection.extraction_rules, &payload);
+                                    if !extracted.is_empty() {
+                                        connection.variables.extend(extracted);
+                                        send_variables(id, connection, &utnw);
+                                    }
+                                    connection
+                                        .messages
+                                        .push_text(Direction::Received, payload.to_owned());
+                                    connection.received_bytes += num_bytes;
+                                    break;
+                                }
+                            }
+                        }
+                        Message::Closed { id, code } => {
+                            let mut state = app_state.lock().unwrap();
+                            if let Some(window) = state.connections.iter_mut().find(|w| w.id == id)
+                            {
+                                let connection = &mut window.connection;
+                                connection.is_connected = false;
+                                connection.connected_at = None;
+                                connection.script_attached = false;
+                                connection.messages.push_control(
+                                    Direction::Received,
+                                    Opcode::Close,
+                                    format!("Connection closed (code: {:?})", code),
+                                );
+                                if let Some((sent_at, input)) = connection.last_sent.take() {
+                                    if sent_at.elapsed() < CRASH_WINDOW && is_crash(code) {
+                                        connection.messages.push_info(String::from(
+                                            "Triaging the last sent message...",
+                                        ));
+                                        let utnw_clone = utnw.clone();
+                                        let ip = render_url(&connection.url);
+                                        tokio::spawn(async move {
+                                            let _ = utnw_clone
+                                                .send(Message::Triage { id, ip, input })
+                                                .await;
+                                        });
+                                    }
+                                }
+                            }
+                        }
+                        Message::TriageDone { id, finding } => {
+                            let mut state = app_state.lock().unwrap();
+                            if let Some(window) = state.connections.iter_mut().find(|w| w.id == id)
+                            {
+                                window.connection.messages.push_info(format!(
+                                    "Triage done: reproducible={}, minimised to {} of {} chars",
+                                    finding.reproducible,
+                                    finding.minimised_input.chars().count(),
+                                    finding.input.chars().count()
+                                ));
+                            }
+                            state.findings.push(finding);
+                            if let Err(e) = store_app(&state) {
+                                eprintln!("Could not store app state: {:?}", e);
+                            }
+                        }
+                        Message::ScenarioStep { id, result } => {
+                            let mut state = app_state.lock().unwrap();
+                            if let Some(run) = state
+                                .connections
+                                .iter_mut()
+                                .find(|w| w.id == id)
+                                .and_then(|w| w.scenario_run.as_mut())
+                            {
+                                run.results.push(result);
+                            }
+                        }
+                        Message::ScenarioDone { id, passed } => {
+                            let mut state = app_state.lock().unwrap();
+                            if let Some(run) = state
+                                .connections
+                                .iter_mut()
+                                .find(|w| w.id == id)
+                                .and_then(|w| w.scenario_run.as_mut())
+                            {
+                                run.passed = Some(passed);
+                            }
+                        }
+                        Message::Sent { id, payload } => {
+                            let mut state = app_state.lock().unwrap();
+                            if let Some(window) = state.connections.iter_mut().find(|w| w.id == id)
+                            {
+                                window.connection.send_bytes += payload.len();
+                                window
+                                    .connection
+                                    .messages
+                                    .push_text(Direction::Sent, payload);
+                            }
+                        }
+                        Message::ScriptLog { id, line, is_error } => {
+                            let mut state = app_state.lock().unwrap();
+                            if let Some(window) = state.connections.iter_mut().find(|w| w.id == id)
+                            {
+                                let prefix = if is_error {
+                                    "[script error]"
+                                } else {
+                                    "[script]"
+                                };
+                                window
+                                    .connection
+                                    .messages
+                                    .push_info(format!("{} {}", prefix, line));
+                            }
+                        }
+                        Message::ScriptStatus { id, attached } => {
+                            let mut state = app_state.lock().unwrap();
+                            if let Some(window) = state.connections.iter_mut().find(|w| w.id == id)
+                            {
+                                window.connection.script_attached = attached;
+                                // A script that does not compile is not sent to new connections
+                                window.connection.script_enabled &= attached;
+                            }
+                        }
+                        Message::SetVariable { id, name, value } => {
+                            let mut state = app_state.lock().unwrap();
+                            if let Some(window) = state.connections.iter_mut().find(|w| w.id == id)
+                            {
+                                window.connection.variables.insert(name, value);
+                                send_variables(id, &window.connection, &utnw);
+                            }
+                        }
+                        Message::Binary { id, data } => {
+                            let mut state = app_state.lock().unwrap();
+                            if let Some(window) = state.connections.iter_mut().find(|w| w.id == id)
+                            {
+                                window.connection.received_bytes += data.len();
+                                log_binary(window, Direction::Received, data);
+                            }
+                        }
+                        Message::Pong { id, rtt } => {
+                            let mut state = app_state.lock().unwrap();
+                            if let Some(window) = state.connections.iter_mut().find(|w| w.id == id)
+                            {
+                                let text = match rtt {
+                                    Some(rtt) => format!("pong ({} ms)", rtt.as_millis()),
+                                    None => String::from("pong"),
+                                };
+                                if rtt.is_some() {
+                                    window.connection.last_rtt = rtt;
+                                }
+                                window.connection.messages.push_control(
+                                    Direction::Received,
+                                    Opcode::Pong,
+                                    text,
+                                );
+                            }
+                        }
+                        Message::NewClient { id, .. } => {
+                            let mut state = app_state.lock().unwrap();
+                            if let Some(window) = state.connections.iter_mut().find(|w| w.id == id)
+                            {
+                                let connection = &mut window.connection;
+                                connection.is_connected = true;
+                                connection.connected_at = Some(Instant::now());
+                                // Every connection starts without a script or auto-responders
+                                let mut messages = Vec::new();
+                                if connection.script_enabled {
+                                    messages.push(Message::SetScript {
+                                        id,
+                                        source: Some(connection.script.clone()),
+                                    });
+                                }
+                                if !connection.auto_responders.is_empty() {
+                                    messages.push(Message::SetAutoResponders {
+                                        id,
+                                        responders: connection.auto_responders.clone(),
+                                    });
+                                }
+                                if !connection.variables.is_empty() {
+                                    messages.push(Message::SetVariables {
+                                        id,
+                                        variables: connection.variables.clone(),
+                                    });
+                                }
+                                let utnw_clone = utnw.clone();
+                                tokio::spawn(async move {
+                                    for message in messages {
+                                        let _ = utnw_clone.send(message).await;
+                                    }
+                                });
+                            }
+                        }
+                        Message::ReplayDone {
+                            golden: Some(name),
+                            report,
+                        } => {
+                            let mut state = app_state.lock().unwrap();
+                            if let Some(golden) = state.goldens.iter_mut().find(|g| g.name == name)
+                            {
+                                golden.last_run = Some(chrono::Utc::now());
+                                golden.last_passed = Some(report.passed());
+                            }
+                            state.golden_runs.insert(name, Some(report));
+                            if let Err(e) = store_app(&state) {
+                                eprintln!("Could not store app state: {:?}", e);
                             }
                         }
-                        println!("Processing a message = {:?}", &payload);
+                        Message::SearchDone { results, error } => {
+                            let mut state = app_state.lock().unwrap();
+                            state.search_running = false;
+                            state.search_results = results;
+                            state.search_error = error;
+                        }
+                        Message::ReplayDone {
+                            golden: None,
+                            report,
+                        } => {
+                            let mut state = app_state.lock().unwrap();
+                            state.replay_running = false;
+                            state.replay_report = Some(report);
+                        }
+                        Message::Close { .. }
+                        | Message::StartRecording { .. }
+                        | Message::StopRecording
+                        | Message::Replay { .. }
+                        | Message::Ping { .. }
+                        | Message::Handshake { .. }
+                        | Message::Triage { .. }
+                        | Message::Search { .. }
+                        | Message::RunScenario { .. }
+                        | Message::SetScript { .. }
+                        | Message::SetAutoResponders { .. }
+                        | Message::SetVariables { .. } => {}
                     }
-                    Message::Close { id } => todo!(),
                 }
-            }
 
-            if should_create_connection {
-                create_connection(app_state.clone(), utnw.clone());
-                should_create_connection = false;
-            }
+                if should_create_connection {
+                    create_connection(app_state.clone(), utnw.clone());
+                    should_create_connection = false;
+                }
 
-            render_windows(ctx, app_state.clone(), utnw.clone());
+                render_windows(ctx, app_state.clone(), utnw.clone());
+                autosave.tick(&app_state.lock().unwrap());
 
-            ctx.request_repaint();
-        })
+                ctx.request_repaint();
+            });
+
+        if let Err(e) = store_app(&self.app_state.lock().unwrap()) {
+            eprintln!("Could not store app state: {:?}", e);
+        }
+        result
     }
 }
 
 pub fn create_connection(app_state: Arc<Mutex<AppState>>, ui_to_network: Sender<Message>) {
     let mut state = app_state.lock().unwrap();
     let editing_ip = state.editing_ip.clone();
-    let id = state.insert_new_window(editing_ip.to_owned());
+    let id = match state.insert_new_window(editing_ip.to_owned()) {
+        Ok(id) => id,
+        Err(e) => {
+            state.open_error = Some(e.to_string());
+            return;
+        }
+    };
+    state.open_error = None;
     let utnw_clone = ui_to_network.clone();
     tokio::spawn(async move {
         let _ = utnw_clone
             .send(Message::NewClient {
                 id: (id),
-                ip: editing_ip.to_string(),
+                ip: render_url(&editing_ip),
             })
             .await;
     });
 }
 
 fn render_windows(ctx: &Context, app_state: Arc<Mutex<AppState>>, ui_to_network: Sender<Message>) {
-    let ui_to_network_clone = ui_to_network.clone();
     let mut actions = Vec::new();
 
-    {
+    egui::CentralPanel::default().show(ctx, |ui| {
         let mut state = app_state.lock().unwrap();
-        for window_index in 0..state.connections.len() {
-            render_connection_window(
-                &mut state,
-                window_index,
-                &ui_to_network_clone,
-                ctx,
-                &mut actions,
-            );
-        }
-    }
+        let ids: Vec<u8> = state.connections.iter().map(|w| w.id).collect();
+        state.layout.sync(&ids);
+        render_panes(ui, &mut state, &ui_to_network, &mut actions);
+    });
 
-    // Process actions
     let mut state = app_state.lock().unwrap();
+    run_jobs(&mut state, &mut actions, &ui_to_network);
+    apply_actions(&mut state, actions);
+
+    let windows_to_remove = state.windows_to_remove.clone();
+    state
+        .connections
+        .retain(|window| !windows_to_remove.contains(&window.id));
+    state.windows_to_remove.clear();
+}
+
+fn apply_actions(state: &mut AppState, actions: Vec<WindowAction>) {
     for action in actions {
         match action {
             WindowAction::Disconnect(id) => {
@@ -138,9 +436,12 @@ fn render_windows(ctx: &Context, app_state: Arc<Mutex<AppState>>, ui_to_network:
             }
             WindowAction::UpdateMessage(id, msg) => {
                 if let Some(window) = state.connections.iter_mut().find(|w| w.id == id) {
-                    window.connection.messages.push(msg.clone());
-                    window.connection.editing_message.clear();
-                    window.connection.send_bytes += msg.as_bytes().len();
+                    window
+                        .connection
+                        .messages
+                        .push_text(Direction::Sent, msg.clone());
+                    window.connection.send_bytes += msg.len();
+                    window.connection.last_sent = Some((Instant::now(), msg));
                 }
             }
             WindowAction::Send(sender, message) => {
@@ -150,179 +451,477 @@ fn render_windows(ctx: &Context, app_state: Arc<Mutex<AppState>>, ui_to_network:
             }
         }
     }
+}
 
-    let windows_to_remove = state.windows_to_remove.clone();
-    state
-        .connections
-        .retain(|window| !windows_to_remove.contains(&window.id));
-    state.windows_to_remove.clear();
+/// Arranges the connection windows in the panes of the layout.
+fn render_panes(
+    ui: &mut egui::Ui,
+    state: &mut std::sync::MutexGuard<'_, AppState>,
+    ui_to_network: &Sender<Message>,
+    actions: &mut Vec<WindowAction>,
+) {
+    const GAP: f32 = 8.;
+    let rect = ui.available_rect_before_wrap();
+    let count = state.layout.panes.len();
+    let mut tab_action = None;
+    for pane_index in 0..count {
+        let (offset, pane_rect) = match state.layout.split {
+            Split::Horizontal => {
+                let width = (rect.width() - GAP * (count - 1) as f32) / count as f32;
+                let offset = pane_index as f32 * (width + GAP);
+                let min = rect.min + egui::vec2(offset, 0.);
+                (
+                    offset,
+                    egui::Rect::from_min_size(min, egui::vec2(width, rect.height())),
+                )
+            }
+            Split::Vertical => {
+                let height = (rect.height() - GAP * (count - 1) as f32) / count as f32;
+                let offset = pane_index as f32 * (height + GAP);
+                let min = rect.min + egui::vec2(0., offset);
+                (
+                    offset,
+                    egui::Rect::from_min_size(min, egui::vec2(rect.width(), height)),
+                )
+            }
+        };
+        if pane_index > 0 {
+            let stroke = ui.visuals().widgets.noninteractive.bg_stroke;
+            let middle = offset - GAP / 2.;
+            match state.layout.split {
+                Split::Horizontal => {
+                    ui.painter()
+                        .vline(rect.left() + middle, rect.y_range(), stroke)
+                }
+                Split::Vertical => ui
+                    .painter()
+                    .hline(rect.x_range(), rect.top() + middle, stroke),
+            }
+        }
+        let mut pane_ui = ui.child_ui(pane_rect, egui::Layout::top_down(egui::Align::Min));
+        pane_ui.set_clip_rect(pane_rect.intersect(ui.clip_rect()));
+        if let Some(action) = render_pane(&mut pane_ui, state, pane_index, ui_to_network, actions) {
+            tab_action = Some(action);
+        }
+    }
+    ui.allocate_rect(rect, egui::Sense::hover());
+
+    match tab_action {
+        Some(TabAction::Show(id)) => state.layout.focus(id),
+        Some(TabAction::Move(id, pane_index)) => state.layout.move_tab(id, pane_index),
+        None => {}
+    }
+}
+
+enum TabAction {
+    Show(u8),
+    /// Moves a tab to the pane at an index, a new pane past the last one
+    Move(u8, usize),
+}
+
+/// Tabs of the pane at `pane_index` and the connection window of its active tab.
+fn render_pane(
+    ui: &mut egui::Ui,
+    state: &mut std::sync::MutexGuard<'_, AppState>,
+    pane_index: usize,
+    ui_to_network: &Sender<Message>,
+    actions: &mut Vec<WindowAction>,
+) -> Option<TabAction> {
+    let pane = state.layout.panes[pane_index].clone();
+    let pane_count = state.layout.panes.len();
+    let mut tab_action = None;
+    ui.horizontal_wrapped(|ui| {
+        for id in &pane.tabs {
+            let Some(window) = state.connections.iter().find(|w| w.id == *id) else {
+                continue;
+            };
+            let color = if window.connection.is_connected {
+                egui::Color32::from_rgb(90, 200, 120)
+            } else {
+                egui::Color32::GRAY
+            };
+            ui.colored_label(color, "●");
+            let response = ui
+                .selectable_label(pane.active == Some(*id), window.title())
+                .on_hover_text(format!(
+                    "{}\nRight-click to move it to another pane",
+                    window.connection.url
+                ));
+            if response.clicked() {
+                tab_action = Some(TabAction::Show(*id));
+            }
+            response.context_menu(|ui| {
+                for target in (0..pane_count).filter(|target| *target != pane_index) {
+                    if ui.button(format!("Move to pane {}", target + 1)).clicked() {
+                        tab_action = Some(TabAction::Move(*id, target));
+                        ui.close_menu();
+                    }
+                }
+                if ui
+                    .add_enabled(pane.tabs.len() > 1, egui::Button::new("Move to new pane"))
+                    .clicked()
+                {
+                    tab_action = Some(TabAction::Move(*id, pane_count));
+                    ui.close_menu();
+                }
+            });
+        }
+    });
+    ui.separator();
+
+    match pane
+        .active
+        .and_then(|id| state.connections.iter().position(|w| w.id == id))
+    {
+        Some(window_index) => {
+            egui::ScrollArea::vertical()
+                .id_source(("pane", state.connections[window_index].id))
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    render_connection_window(ui, state, window_index, ui_to_network, actions);
+                });
+        }
+        None => {
+            ui.label("Create a connection or open a profile from the Connection Manager");
+        }
+    }
+    tab_action
 }
 
 fn render_connection_window(
+    ui: &mut egui::Ui,
     state: &mut std::sync::MutexGuard<'_, AppState>,
     window_index: usize,
     ui_to_network_clone: &Sender<Message>,
-    ctx: &Context,
     actions: &mut Vec<WindowAction>,
 ) {
-    let window_id = state.connections[window_index].id.clone();
+    let window_id = state.connections[window_index].id;
     let utn_for_send = ui_to_network_clone.clone();
     let utn_for_disconnect = ui_to_network_clone.clone();
 
-    egui::Window::new(&window_id.to_string())
-        .resizable(true)
-        .show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                ui.label("Ip Address:");
-                ui.label(&state.connections[window_index].connection.url);
-                if ui.button("Disconnect").clicked() {
-                    actions.push(WindowAction::Disconnect(window_id));
-                    actions.push(WindowAction::Send(
-                        utn_for_disconnect,
-                        Message::Close {
-                            id: window_id.clone(),
-                        },
-                    ));
-                }
-            });
+    ui.horizontal(|ui| {
+        ui.label("Ip Address:");
+        ui.label(&state.connections[window_index].connection.url);
+        if ui.button("Disconnect").clicked() {
+            state.connections[window_index].connection.last_sent = None;
+            actions.push(WindowAction::Disconnect(window_id));
+            actions.push(WindowAction::Send(
+                utn_for_disconnect,
+                Message::Close {
+                    id: window_id,
+                    code: None,
+                    reason: String::new(),
+                },
+            ));
+        }
+    });
 
-            ui.separator();
+    ui.horizontal(|ui| {
+        let state: &mut AppState = state;
+        ui.label("Profile:");
+        let window = &mut state.connections[window_index];
+        ui.text_edit_singleline(&mut window.editing_profile);
+        if ui
+            .button("Save as Profile")
+            .on_hover_text("Saves the URL, send options, script and rules to the workspace")
+            .clicked()
+        {
+            let name = window.editing_profile.trim().to_string();
+            if name.is_empty() {
+                state.workspace_error = Some(String::from("Profile name is empty"));
+            } else {
+                let profile = ConnectionProfile::from_window(name, window);
+                let workspace = state.workspace_mut();
+                workspace.profiles.retain(|p| p.name != profile.name);
+                workspace.profiles.push(profile);
+                state.workspace_error = None;
+            }
+        }
+    });
 
-            ui.horizontal(|ui| {
-                ui.label(format!(
-                    "Sent / Recv [{} / {}] bytes",
-                    state.connections[window_index].connection.send_bytes,
-                    state.connections[window_index].connection.received_bytes
-                ));
-            });
+    ui.horizontal(|ui| {
+        ui.label("Name:");
+        ui.add(
+            egui::TextEdit::singleline(&mut state.connections[window_index].name)
+                .hint_text(format!("Connection {}", window_id))
+                .desired_width(160.),
+        )
+        .on_hover_text("Shown as the title of the tab");
+        ui.label("Group:");
+        ui.add(
+            egui::TextEdit::singleline(&mut state.connections[window_index].group)
+                .hint_text("e.g. bots")
+                .desired_width(120.),
+        )
+        .on_hover_text("Connections of a group can be selected together to broadcast to");
+    });
 
-            ui.separator();
+    ui.separator();
 
-            ui.horizontal(|ui| {
-                ui.label("Messages:");
-                egui::ScrollArea::vertical()
-                    .min_scrolled_height(400.)
-                    .stick_to_bottom(true)
-                    .show(ui, |ui| {
-                        ui.vertical(|ui| {
-                            for message in &state.connections[window_index].connection.messages {
-                                ui.horizontal(|ui| {
-                                    if ui.button("📋").on_hover_text("Click to copy").clicked() {
-                                        ui.output_mut(|o| o.copied_text = message.to_string());
-                                    }
-                                    ui.add(egui::Label::new(message).wrap(true));
-                                });
-                            }
-                        });
-                    });
-            });
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "Sent / Recv [{} / {}] bytes",
+            state.connections[window_index].connection.send_bytes,
+            state.connections[window_index].connection.received_bytes
+        ));
+    });
 
-            ui.separator();
+    ui.separator();
 
-            ui.label("Send Options:");
-            ui.horizontal(|ui| {
-                ui.radio_value(
-                    &mut state.connections[window_index].send_option,
-                    SendOptions::Manual,
-                    "Manual",
-                );
-                ui.radio_value(
-                    &mut state.connections[window_index].send_option,
-                    SendOptions::Periodically,
-                    "Periodically",
-                );
-                ui.radio_value(
-                    &mut state.connections[window_index].send_option,
-                    SendOptions::Random,
-                    "Random",
-                );
-                ui.radio_value(
-                    &mut state.connections[window_index].send_option,
-                    SendOptions::File,
-                    "File",
-                );
-            });
+    render_messages(ui, state, window_index);
 
-            match state.connections[window_index].send_option {
-                SendOptions::Periodically => {
-                    ui.vertical(|ui| {
-                        ui.label("Period (ms):");
-                        ui.add(egui::TextEdit::singleline(
-                            &mut state.connections[window_index].editing_period,
-                        ));
+    ui.separator();
 
-                        ui.label("Quantity (-1 = infinite)");
-                        ui.add(egui::TextEdit::singleline(
-                            &mut state.connections[window_index].editing_period,
-                        ));
+    render_variables(ui, state, window_index);
 
-                        if state.connections[window_index].connection.job_running {
-                            if ui.button("Cancel").clicked() {
-                                state.connections[window_index].connection.job_running = false;
-                                // TODO: Make sure to interrupt the interval task
-                            }
-                        } else {
-                            if ui.button("Start").clicked() {
-                                state.connections[window_index].connection.job_running = true;
-                                // TODO: Start the task that sends messages periodically
-                            }
-                        }
-                    });
-                    render_chat_input(ui, state, window_index, actions, window_id, utn_for_send);
-                }
-                SendOptions::Random => {
-                    ui.vertical(|ui| {
-                        ui.label("Period (ms):");
-                        ui.add(egui::TextEdit::singleline(
-                            &mut state.connections[window_index].editing_period,
-                        ));
+    ui.separator();
 
-                        ui.label("Min Length:");
-                        ui.add(egui::TextEdit::singleline(
-                            &mut state.connections[window_index].editing_period,
-                        ));
+    render_scenario(ui, state, window_index, actions, ui_to_network_clone);
 
-                        ui.label("Max Length:");
-                        ui.add(egui::TextEdit::singleline(
-                            &mut state.connections[window_index].editing_period,
-                        ));
-                        ui.horizontal(|ui| {
-                            ui.label("Contains Letters:");
-                            ui.checkbox(
-                                &mut state.connections[window_index].connection.job_running,
-                                "",
-                            );
+    ui.separator();
 
-                            ui.label("Contains Numbers:");
-                            ui.checkbox(
-                                &mut state.connections[window_index].connection.job_running,
-                                "",
-                            );
+    render_script(ui, state, window_index, actions, ui_to_network_clone);
 
-                            ui.label("Contains Symbols:");
-                            ui.checkbox(
-                                &mut state.connections[window_index].connection.job_running,
-                                "",
-                            );
-                        });
+    ui.separator();
 
-                        if state.connections[window_index].connection.job_running {
-                            if ui.button("Cancel").clicked() {
-                                state.connections[window_index].connection.job_running = false;
-                            }
-                        } else {
-                            if ui.button("Start").clicked() {
-                                state.connections[window_index].connection.job_running = true;
-                            }
-                        }
-                    });
-                }
-                SendOptions::Manual => {
-                    render_chat_input(ui, state, window_index, actions, window_id, utn_for_send);
-                }
-                SendOptions::File => {}
+    render_auto_responders(ui, state, window_index, actions, ui_to_network_clone);
+
+    ui.separator();
+
+    render_codec(ui, state, window_index);
+
+    ui.separator();
+
+    ui.label("Send Options:");
+    ui.horizontal(|ui| {
+        ui.radio_value(
+            &mut state.connections[window_index].send_option,
+            SendOptions::Manual,
+            "Manual",
+        );
+        ui.radio_value(
+            &mut state.connections[window_index].send_option,
+            SendOptions::Periodically,
+            "Periodically",
+        );
+        ui.radio_value(
+            &mut state.connections[window_index].send_option,
+            SendOptions::Random,
+            "Random",
+        );
+        ui.radio_value(
+            &mut state.connections[window_index].send_option,
+            SendOptions::File,
+            "File",
+        );
+    });
+
+    match state.connections[window_index].send_option {
+        SendOptions::Periodically => {
+            ui.vertical(|ui| {
+                let window = &mut state.connections[window_index];
+                ui.label("Period (ms):");
+                ui.add(egui::TextEdit::singleline(&mut window.editing_period));
+
+                ui.label("Quantity (-1 = infinite)");
+                ui.add(egui::TextEdit::singleline(&mut window.job.quantity));
+
+                render_job_button(ui, window);
+            });
+            render_chat_input(ui, state, window_index, actions, utn_for_send);
+        }
+        SendOptions::Random => {
+            ui.vertical(|ui| {
+                let window = &mut state.connections[window_index];
+                ui.label("Period (ms):");
+                ui.add(egui::TextEdit::singleline(&mut window.editing_period));
+
+                ui.label("Quantity (-1 = infinite)");
+                ui.add(egui::TextEdit::singleline(&mut window.job.quantity));
+
+                ui.label("Min Length:");
+                ui.add(egui::TextEdit::singleline(&mut window.job.min_length));
+
+                ui.label("Max Length:");
+                ui.add(egui::TextEdit::singleline(&mut window.job.max_length));
+                ui.horizontal(|ui| {
+                    ui.label("Contains Letters:");
+                    ui.checkbox(&mut window.job.letters, "");
+
+                    ui.label("Contains Numbers:");
+                    ui.checkbox(&mut window.job.digits, "");
+
+                    ui.label("Contains Symbols:");
+                    ui.checkbox(&mut window.job.symbols, "");
+                });
+
+                render_job_button(ui, window);
+            });
+        }
+        SendOptions::Manual => {
+            render_chat_input(ui, state, window_index, actions, utn_for_send);
+        }
+        SendOptions::File => {}
+    }
+}
+
+/// Starts or cancels the window's periodic or random job, which `run_jobs` drives.
+fn render_job_button(ui: &mut egui::Ui, window: &mut ConnectionWindow) {
+    let connection = &mut window.connection;
+    if connection.job_running {
+        ui.horizontal(|ui| {
+            if ui.button("Cancel").clicked() {
+                connection.job_running = false;
             }
+            ui.label(format!("{} sent", connection.job_sent));
         });
+    } else if ui
+        .button("Start")
+        .on_hover_text("Sends while the connection is open")
+        .clicked()
+    {
+        start_job(window);
+    }
+}
+
+/// Starts the job of the window's send option, if it has one.
+fn start_job(window: &mut ConnectionWindow) {
+    let connection = &mut window.connection;
+    connection.job_running = matches!(
+        window.send_option,
+        SendOptions::Periodically | SendOptions::Random
+    );
+    connection.job_sent = 0;
+    connection.job_next = None;
+}
+
+/// What a job sends next.
+enum JobMessage {
+    /// The composer's message, expanded like a manual send
+    Template(String),
+    Random(String),
+}
+
+/// The period of the window's job, how many messages it sends (`None` for no limit) and its
+/// next message.
+fn next_job_message(
+    window: &ConnectionWindow,
+) -> Result<(Duration, Option<u64>, JobMessage), String> {
+    let period: u64 = window
+        .editing_period
+        .trim()
+        .parse()
+        .ok()
+        .filter(|period| *period > 0)
+        .ok_or("The period must be a positive number of milliseconds")?;
+    let quantity: i64 = window
+        .job
+        .quantity
+        .trim()
+        .parse()
+        .map_err(|_| "The quantity must be a number, -1 for no limit")?;
+    let message = match window.send_option {
+        SendOptions::Random => {
+            let length = |text: &str| {
+                text.trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("{:?} is not a length", text))
+            };
+            let min_length = length(&window.job.min_length)?;
+            let max_length = length(&window.job.max_length)?;
+            if min_length > max_length {
+                return Err(String::from("The min length is above the max length"));
+            }
+            let mut alphabet = Vec::new();
+            if window.job.letters {
+                alphabet.extend((b'a'..=b'z').chain(b'A'..=b'Z'));
+            }
+            if window.job.digits {
+                alphabet.extend(b'0'..=b'9');
+            }
+            if window.job.symbols {
+                alphabet.extend((b'!'..=b'~').filter(u8::is_ascii_punctuation));
+            }
+            if alphabet.is_empty() {
+                return Err(String::from("Pick at least one kind of character"));
+            }
+            JobMessage::Random(random_text(min_length, max_length, &alphabet))
+        }
+        _ => JobMessage::Template(window.connection.editing_message.clone()),
+    };
+    Ok((
+        Duration::from_millis(period),
+        u64::try_from(quantity).ok(),
+        message,
+    ))
+}
+
+/// Sends the next message of every running job that is due. Jobs wait while their connection
+/// is closed.
+fn run_jobs(
+    state: &mut AppState,
+    actions: &mut Vec<WindowAction>,
+    ui_to_network: &Sender<Message>,
+) {
+    let now = Instant::now();
+    for window_index in 0..state.connections.len() {
+        let window = &mut state.connections[window_index];
+        let connection = &window.connection;
+        if !connection.job_running
+            || !connection.is_connected
+            || connection.job_next.is_some_and(|next| next > now)
+        {
+            continue;
+        }
+        let (period, quantity, message) = match next_job_message(window) {
+            Ok(job) => job,
+            Err(e) => {
+                window.connection.job_running = false;
+                window
+                    .connection
+                    .messages
+                    .push_info(format!("Job stopped: {}", e));
+                continue;
+            }
+        };
+        if quantity.is_some_and(|quantity| window.connection.job_sent >= quantity) {
+            window.connection.job_running = false;
+            continue;
+        }
+        let sent = match message {
+            JobMessage::Template(template) => send_template(
+                state,
+                window_index,
+                &template,
+                actions,
+                ui_to_network.clone(),
+            ),
+            JobMessage::Random(payload) => {
+                let id = window.id;
+                actions.push(WindowAction::UpdateMessage(id, payload.clone()));
+                actions.push(WindowAction::Send(
+                    ui_to_network.clone(),
+                    Message::Payload {
+                        id,
+                        payload,
+                        num_bytes: 0,
+                    },
+                ));
+                true
+            }
+        };
+        let connection = &mut state.connections[window_index].connection;
+        if !sent {
+            // send_template logged why
+            connection.job_running = false;
+            continue;
+        }
+        connection.job_sent += 1;
+        connection.job_next = Some(now + period);
+        if quantity.is_some_and(|quantity| connection.job_sent >= quantity) {
+            connection.job_running = false;
+        }
+    }
 }
 
 fn render_chat_input(
@@ -330,9 +929,9 @@ fn render_chat_input(
     state: &mut std::sync::MutexGuard<'_, AppState>,
     window_index: usize,
     actions: &mut Vec<WindowAction>,
-    window_id: u8,
     utn_for_send: Sender<Message>,
 ) {
+    render_snippet_menu(ui, state, window_index, actions, &utn_for_send);
     ui.horizontal(|ui| {
         if ui
             .add_sized(
@@ -341,23 +940,2478 @@ fn render_chat_input(
                     &mut state.connections[window_index].connection.editing_message,
                 ),
             )
+            .on_hover_text(TEMPLATE_HINT)
             .changed()
+            && ui.input(|ev| ev.key_pressed(egui::Key::Enter))
         {
-            if ui.input(|ev| ev.key_pressed(egui::Key::Enter)) {
-                let msg = state.connections[window_index]
+            let template = state.connections[window_index]
+                .connection
+                .editing_message
+                .clone();
+            if send_template(state, window_index, &template, actions, utn_for_send) {
+                state.connections[window_index]
                     .connection
                     .editing_message
-                    .clone();
-                actions.push(WindowAction::UpdateMessage(window_id, msg.clone()));
-                actions.push(WindowAction::Send(
-                    utn_for_send,
-                    Message::Message {
-                        id: window_id,
-                        payload: msg,
-                        num_bytes: 0,
-                    },
-                ));
+                    .clear();
             }
         }
     });
 }
+
+/// Gives the connection's task the variables its auto-responders render responses with.
+fn send_variables(id: u8, connection: &Connection, utnw: &Sender<Message>) {
+    if !connection.is_connected {
+        return;
+    }
+    let message = Message::SetVariables {
+        id,
+        variables: connection.variables.clone(),
+    };
+    let utnw = utnw.clone();
+    tokio::spawn(async move {
+        let _ = utnw.send(message).await;
+    });
+}
+
+/// Expands `template` for the window's connection and sends it. Returns false when it could
+/// not be encoded by the window's codec.
+fn send_template(
+    state: &mut AppState,
+    window_index: usize,
+    template: &str,
+    actions: &mut Vec<WindowAction>,
+    utn_for_send: Sender<Message>,
+) -> bool {
+    let window_id = state.connections[window_index].id;
+    let connection = &mut state.connections[window_index].connection;
+    let context = TemplateContext {
+        conn_id: window_id,
+        counter: connection.send_counter,
+        variables: &connection.variables,
+    };
+    connection.send_counter += 1;
+    let msg = render_template(template, &context);
+
+    // With a codec, the composer holds JSON that is sent encoded as a binary frame
+    let window = &mut state.connections[window_index];
+    if let Some(codec) = &window.codec {
+        let encoded = serde_json::from_str::<Value>(&msg)
+            .map_err(anyhow::Error::from)
+            .and_then(|value| Ok((codec.encode(&value)?, value)));
+        let messages = &mut window.connection.messages;
+        return match encoded {
+            Ok((data, value)) => {
+                window.connection.send_bytes += data.len();
+                messages.push_decoded(
+                    Direction::Sent,
+                    data.clone(),
+                    codec.name(),
+                    value.to_string(),
+                );
+                actions.push(WindowAction::Send(
+                    utn_for_send,
+                    Message::Binary {
+                        id: window_id,
+                        data,
+                    },
+                ));
+                true
+            }
+            Err(e) => {
+                messages.push_info(format!(
+                    "Could not encode the message as {}: {}",
+                    codec.name(),
+                    e
+                ));
+                false
+            }
+        };
+    }
+    actions.push(WindowAction::UpdateMessage(window_id, msg.clone()));
+    actions.push(WindowAction::Send(
+        utn_for_send,
+        Message::Payload {
+            id: window_id,
+            payload: msg,
+            num_bytes: 0,
+        },
+    ));
+    true
+}
+
+/// Logs a binary frame, decoded by the window's codec when it has one.
+fn log_binary(window: &mut ConnectionWindow, direction: Direction, data: Vec<u8>) {
+    let messages = &mut window.connection.messages;
+    let Some(codec) = &window.codec else {
+        messages.push_binary(direction, data);
+        return;
+    };
+    match codec.decode(&data) {
+        Ok(value) => messages.push_decoded(direction, data, codec.name(), value.to_string()),
+        Err(e) => {
+            messages.push_binary(direction, data);
+            messages.push_info(format!(
+                "Could not decode the frame as {}: {}",
+                codec.name(),
+                e
+            ));
+        }
+    }
+}
+
+/// Menu of the snippet library, by folder, to insert a snippet into the editor or send it.
+fn render_snippet_menu(
+    ui: &mut egui::Ui,
+    state: &mut AppState,
+    window_index: usize,
+    actions: &mut Vec<WindowAction>,
+    ui_to_network: &Sender<Message>,
+) {
+    if state.snippets.is_empty() {
+        return;
+    }
+    let mut picked: Option<(Snippet, bool)> = None;
+    ui.menu_button("Snippets", |ui| {
+        for folder in folders(&state.snippets) {
+            let mut add_items = |ui: &mut egui::Ui| {
+                for snippet in state.snippets.iter().filter(|s| s.folder == folder) {
+                    ui.horizontal(|ui| {
+                        let text = snippet.kind == SnippetKind::Text;
+                        if ui
+                            .add_enabled(text, egui::Button::new("Insert"))
+                            .on_disabled_hover_text("Binary snippets can only be sent")
+                            .clicked()
+                        {
+                            picked = Some((snippet.clone(), false));
+                            ui.close_menu();
+                        }
+                        if ui.button("Send").clicked() {
+                            picked = Some((snippet.clone(), true));
+                            ui.close_menu();
+                        }
+                        ui.label(&snippet.name);
+                    });
+                }
+            };
+            if folder.is_empty() {
+                add_items(ui);
+            } else {
+                ui.menu_button(format!("📁 {}", folder), add_items);
+            }
+        }
+    });
+
+    match picked {
+        Some((snippet, false)) => {
+            state.connections[window_index].connection.editing_message = snippet.payload;
+        }
+        Some((snippet, true)) => match snippet.kind {
+            SnippetKind::Text => {
+                send_template(
+                    state,
+                    window_index,
+                    &snippet.payload,
+                    actions,
+                    ui_to_network.clone(),
+                );
+            }
+            SnippetKind::Binary => {
+                let data = parse_hex(&snippet.payload).unwrap_or_default();
+                let window = &mut state.connections[window_index];
+                window.connection.send_bytes += data.len();
+                log_binary(window, Direction::Sent, data.clone());
+                actions.push(WindowAction::Send(
+                    ui_to_network.clone(),
+                    Message::Binary {
+                        id: window.id,
+                        data,
+                    },
+                ));
+            }
+        },
+        None => {}
+    }
+}
+
+fn render_snippets(ui: &mut egui::Ui, state: &mut std::sync::MutexGuard<'_, AppState>) {
+    let state: &mut AppState = state;
+    CollapsingHeader::new(format!("Snippets ({})", state.snippets.len()))
+        .id_source("snippets")
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Filter:");
+                ui.text_edit_singleline(&mut state.snippet_filter)
+                    .on_hover_text("Matches names, folders and tags");
+            });
+
+            let mut to_edit = None;
+            let mut to_remove = None;
+            let mut to_move = None;
+            for folder in folders(&state.snippets) {
+                let indices: Vec<usize> = (0..state.snippets.len())
+                    .filter(|i| state.snippets[*i].folder == folder)
+                    .filter(|i| matches_filter(&state.snippets[*i], &state.snippet_filter))
+                    .collect();
+                if indices.is_empty() {
+                    continue;
+                }
+                let add_rows = |ui: &mut egui::Ui| {
+                    for index in indices {
+                        let snippet = &state.snippets[index];
+                        let row = ui.horizontal(|ui| {
+                            let handle = ui
+                                .add(egui::Label::new("☰").sense(egui::Sense::drag()))
+                                .on_hover_cursor(egui::CursorIcon::Grab)
+                                .on_hover_text("Drag to reorder");
+                            if handle.drag_started() {
+                                state.dragged_snippet = Some(index);
+                            }
+                            if ui.button("✏").on_hover_text("Edit snippet").clicked() {
+                                to_edit = Some(index);
+                            }
+                            if ui.button("🗑").on_hover_text("Delete snippet").clicked() {
+                                to_remove = Some(index);
+                            }
+                            let kind = match snippet.kind {
+                                SnippetKind::Text => "",
+                                SnippetKind::Binary => " [binary]",
+                            };
+                            let tags: String =
+                                snippet.tags.iter().map(|t| format!(" #{}", t)).collect();
+                            ui.label(format!("{}{}{}", snippet.name, kind, tags))
+                                .on_hover_text(&snippet.payload);
+                        });
+                        if let Some(from) = state.dragged_snippet {
+                            if from != index && ui.rect_contains_pointer(row.response.rect) {
+                                let rect = row.response.rect;
+                                let y = if from < index {
+                                    rect.bottom()
+                                } else {
+                                    rect.top()
+                                };
+                                ui.painter().hline(
+                                    rect.x_range(),
+                                    y,
+                                    ui.visuals().selection.stroke,
+                                );
+                                if ui.input(|i| i.pointer.any_released()) {
+                                    to_move = Some((from, index));
+                                }
+                            }
+                        }
+                    }
+                };
+                if folder.is_empty() {
+                    add_rows(ui);
+                } else {
+                    CollapsingHeader::new(format!("📁 {}", folder))
+                        .id_source(("snippet_folder", folder.as_str()))
+                        .default_open(true)
+                        .show(ui, add_rows);
+                }
+            }
+            if ui.input(|i| i.pointer.any_released()) {
+                state.dragged_snippet = None;
+            }
+            if let Some((from, to)) = to_move {
+                move_snippet(&mut state.snippets, from, to);
+                // The snippet being edited keeps its place in the new order
+                state.editing_snippet_index = state.editing_snippet_index.map(|i| match i {
+                    i if i == from => to,
+                    i if from < i && i <= to => i - 1,
+                    i if to <= i && i < from => i + 1,
+                    i => i,
+                });
+            }
+            if let Some(index) = to_edit {
+                let snippet = state.snippets[index].clone();
+                state.editing_snippet_tags = snippet.tags.join(", ");
+                state.editing_snippet = snippet;
+                state.editing_snippet_index = Some(index);
+            }
+            if let Some(index) = to_remove {
+                state.snippets.remove(index);
+                state.editing_snippet_index = match state.editing_snippet_index {
+                    Some(i) if i == index => None,
+                    Some(i) if i > index => Some(i - 1),
+                    other => other,
+                };
+            }
+
+            ui.separator();
+            let editing = &mut state.editing_snippet;
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                ui.add(egui::TextEdit::singleline(&mut editing.name).desired_width(120.));
+                ui.label("Folder:");
+                ui.add(egui::TextEdit::singleline(&mut editing.folder).desired_width(100.));
+                ui.label("Tags:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut state.editing_snippet_tags)
+                        .desired_width(120.)
+                        .hint_text("auth, login"),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut editing.kind, SnippetKind::Text, "Text");
+                ui.radio_value(&mut editing.kind, SnippetKind::Binary, "Binary (hex)");
+            });
+            let hint = match editing.kind {
+                SnippetKind::Text => TEMPLATE_HINT,
+                SnippetKind::Binary => "Hex digits, e.g. de ad be ef",
+            };
+            ui.add(
+                egui::TextEdit::multiline(&mut editing.payload)
+                    .desired_rows(3)
+                    .desired_width(f32::INFINITY),
+            )
+            .on_hover_text(hint);
+            let mut save = false;
+            ui.horizontal(|ui| {
+                save = ui
+                    .button("Save Snippet")
+                    .on_hover_text(
+                        "Replaces the snippet being edited, or the one with the same name in \
+                        the folder",
+                    )
+                    .clicked();
+                if state.editing_snippet_index.is_some() && ui.button("New Snippet").clicked() {
+                    state.editing_snippet = Snippet::default();
+                    state.editing_snippet_tags.clear();
+                    state.editing_snippet_index = None;
+                }
+            });
+            if save {
+                match validate_snippet(&state.editing_snippet) {
+                    Ok(()) => {
+                        let mut snippet = std::mem::take(&mut state.editing_snippet);
+                        snippet.tags = std::mem::take(&mut state.editing_snippet_tags)
+                            .split(',')
+                            .map(|tag| tag.trim().to_string())
+                            .filter(|tag| !tag.is_empty())
+                            .collect();
+                        let edited = state
+                            .editing_snippet_index
+                            .take()
+                            .filter(|i| *i < state.snippets.len());
+                        let same_name = state
+                            .snippets
+                            .iter()
+                            .position(|s| s.name == snippet.name && s.folder == snippet.folder);
+                        match (edited, same_name) {
+                            (Some(index), Some(other)) if other != index => {
+                                // Renamed onto another snippet, which it replaces
+                                state.snippets[index] = snippet;
+                                state.snippets.remove(other);
+                            }
+                            (Some(index), _) | (None, Some(index)) => {
+                                state.snippets[index] = snippet
+                            }
+                            (None, None) => state.snippets.push(snippet),
+                        }
+                        state.snippet_error = None;
+                    }
+                    Err(e) => state.snippet_error = Some(e.to_string()),
+                }
+            }
+            if let Some(error) = &state.snippet_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+        });
+}
+
+fn render_workspace(
+    ui: &mut egui::Ui,
+    state: &mut std::sync::MutexGuard<'_, AppState>,
+    ui_to_network: &Sender<Message>,
+) {
+    let state: &mut AppState = state;
+    ui.horizontal(|ui| {
+        ui.label("Workspace:");
+        let names: Vec<String> = state.workspaces.iter().map(|w| w.name.clone()).collect();
+        egui::ComboBox::from_id_source("workspace")
+            .selected_text(state.active_workspace.as_str())
+            .show_ui(ui, |ui| {
+                for name in names {
+                    let label = name.clone();
+                    ui.selectable_value(&mut state.active_workspace, name, label);
+                }
+            });
+        ui.text_edit_singleline(&mut state.editing_workspace);
+        if ui.button("New Workspace").clicked() {
+            let name = state.editing_workspace.trim().to_string();
+            if name.is_empty() {
+                state.workspace_error = Some(String::from("Workspace name is empty"));
+            } else {
+                state.active_workspace = name;
+                state.workspace_mut();
+                state.editing_workspace.clear();
+                state.workspace_error = None;
+            }
+        }
+    });
+
+    let profile_count = state.workspace().map_or(0, |w| w.profiles.len());
+    CollapsingHeader::new(format!("Profiles ({})", profile_count))
+        .id_source("profiles")
+        .default_open(true)
+        .show(ui, |ui| {
+            let mut to_open = None;
+            let mut to_remove = None;
+            if let Some(workspace) = state.workspace() {
+                for (index, profile) in workspace.profiles.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.button("Open").clicked() {
+                            to_open = Some(profile.clone());
+                        }
+                        if ui.button("🗑").on_hover_text("Delete profile").clicked() {
+                            to_remove = Some(index);
+                        }
+                        ui.label(format!("{}  {}", profile.name, profile.url));
+                    });
+                }
+            }
+            if let Some(index) = to_remove {
+                state.workspace_mut().profiles.remove(index);
+            }
+            if let Some(profile) = to_open {
+                open_profile(state, &profile, ui_to_network);
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("File:");
+                ui.text_edit_singleline(&mut state.workspace_path);
+                if ui.button("Export").clicked() {
+                    let path = state.workspace_path.clone();
+                    let result = export_workspace(state.workspace_mut(), Path::new(&path));
+                    state.workspace_error = result.err().map(|e| e.to_string());
+                }
+                if ui
+                    .button("Import")
+                    .on_hover_text("Replaces the workspace with the same name")
+                    .clicked()
+                {
+                    match import_workspace(Path::new(&state.workspace_path)) {
+                        Ok(workspace) => {
+                            state.active_workspace = workspace.name.clone();
+                            add_workspace(state, workspace);
+                            state.workspace_error = None;
+                        }
+                        Err(e) => state.workspace_error = Some(e.to_string()),
+                    }
+                }
+                if ui
+                    .button("Delete Workspace")
+                    .on_hover_text("Deletes the workspace and its profiles")
+                    .clicked()
+                {
+                    let name = state.active_workspace.clone();
+                    state.workspaces.retain(|w| w.name != name);
+                    state.active_workspace = state
+                        .workspaces
+                        .first()
+                        .map_or_else(String::new, |w| w.name.clone());
+                }
+            });
+        });
+    if let Some(error) = &state.workspace_error {
+        ui.colored_label(egui::Color32::RED, error);
+    }
+}
+
+fn render_environment(ui: &mut egui::Ui, state: &mut std::sync::MutexGuard<'_, AppState>) {
+    let state: &mut AppState = state;
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Environment:");
+        let names: Vec<String> = state.environments.iter().map(|e| e.name.clone()).collect();
+        egui::ComboBox::from_id_source("environment")
+            .selected_text(state.active_environment.as_str())
+            .show_ui(ui, |ui| {
+                for name in names {
+                    let label = name.clone();
+                    changed |= ui
+                        .selectable_value(&mut state.active_environment, name, label)
+                        .changed();
+                }
+            });
+        ui.text_edit_singleline(&mut state.editing_environment);
+        if ui.button("New Environment").clicked() {
+            let name = state.editing_environment.trim().to_string();
+            if name.is_empty() {
+                state.environment_error = Some(String::from("Environment name is empty"));
+            } else {
+                state.active_environment = name;
+                state.environment_mut();
+                state.editing_environment.clear();
+                state.environment_error = None;
+                changed = true;
+            }
+        }
+    });
+
+    let variable_count = state
+        .environment()
+        .map_or(0, |e| e.variables.len() + e.secrets.len());
+    CollapsingHeader::new(format!("Environment Variables ({})", variable_count))
+        .id_source("environment_variables")
+        .show(ui, |ui| {
+            ui.label("Used as {{env.NAME}} in URLs and messages");
+            let mut to_remove = None;
+            if let Some(environment) = state.environment() {
+                let secrets = environment.secrets.keys().map(|name| (name, MASK));
+                let variables = environment
+                    .variables
+                    .iter()
+                    .map(|(name, value)| (name, value.as_str()));
+                for (name, value) in variables.chain(secrets) {
+                    ui.horizontal(|ui| {
+                        if ui.button("🗑").on_hover_text("Remove variable").clicked() {
+                            to_remove = Some(name.clone());
+                        }
+                        ui.label(format!("{} = {}", name, value));
+                    });
+                }
+            }
+            if let Some(name) = to_remove {
+                let environment = state.environment_mut();
+                environment.variables.remove(&name);
+                environment.secrets.remove(&name);
+                changed = true;
+            }
+
+            let editing = &mut state.editing_variable;
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                ui.add(egui::TextEdit::singleline(&mut editing.name).desired_width(120.));
+                ui.label("Value:");
+                ui.add(egui::TextEdit::singleline(&mut editing.value).password(editing.secret));
+                ui.checkbox(&mut editing.secret, "Secret").on_hover_text(
+                    "Stored in a separate secret store, not in the state file or exports, \
+                     and masked in messages and logs",
+                );
+            });
+            if ui.button("Set Variable").clicked() {
+                let variable = std::mem::take(&mut state.editing_variable);
+                let name = variable.name.trim().to_string();
+                if name.is_empty() {
+                    state.environment_error = Some(String::from("Variable name is empty"));
+                } else {
+                    let environment = state.environment_mut();
+                    environment.variables.remove(&name);
+                    environment.secrets.remove(&name);
+                    if variable.secret {
+                        environment.secrets.insert(name, variable.value);
+                    } else {
+                        environment.variables.insert(name, variable.value);
+                    }
+                    state.environment_error = None;
+                    changed = true;
+                }
+            }
+        });
+    if let Some(error) = &state.environment_error {
+        ui.colored_label(egui::Color32::RED, error);
+    }
+    if changed {
+        environment::activate(state.environment());
+    }
+}
+
+/// Opens a connection window with the profile's settings and connects it.
+fn open_profile(
+    state: &mut AppState,
+    profile: &ConnectionProfile,
+    ui_to_network: &Sender<Message>,
+) {
+    let id = match state.open_profile(profile) {
+        Ok(id) => id,
+        Err(e) => {
+            state.open_error = Some(e.to_string());
+            return;
+        }
+    };
+    state.open_error = None;
+    // The script and auto-responders follow once the connection is open
+    let message = Message::NewClient {
+        id,
+        ip: render_url(&profile.url),
+    };
+    let ui_to_network = ui_to_network.clone();
+    tokio::spawn(async move {
+        let _ = ui_to_network.send(message).await;
+    });
+}
+
+fn render_findings(ui: &mut egui::Ui, state: &mut std::sync::MutexGuard<'_, AppState>) {
+    CollapsingHeader::new(format!("Findings ({})", state.findings.len()))
+        .default_open(false)
+        .show(ui, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(300.)
+                .show(ui, |ui| {
+                    for finding in state.findings.iter().rev() {
+                        ui.label(format!(
+                            "{} {} code={:?} reproducible={}",
+                            finding.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                            finding.url,
+                            finding.close_code,
+                            finding.reproducible
+                        ));
+                        ui.horizontal(|ui| {
+                            if ui.button("📋").on_hover_text("Copy input").clicked() {
+                                ui.output_mut(|o| o.copied_text = finding.input.clone());
+                            }
+                            ui.add(
+                                egui::Label::new(format!("Input: {}", finding.input)).wrap(true),
+                            );
+                        });
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button("📋")
+                                .on_hover_text("Copy minimised input")
+                                .clicked()
+                            {
+                                ui.output_mut(|o| o.copied_text = finding.minimised_input.clone());
+                            }
+                            ui.add(
+                                egui::Label::new(format!("Minimised: {}", finding.minimised_input))
+                                    .wrap(true),
+                            );
+                        });
+                        ui.separator();
+                    }
+                });
+            if !state.findings.is_empty() && ui.button("Clear Findings").clicked() {
+                state.findings.clear();
+                if let Err(e) = store_app(state) {
+                    eprintln!("Could not store app state: {:?}", e);
+                }
+            }
+        });
+}
+
+fn render_recording(
+    ui: &mut egui::Ui,
+    state: &mut std::sync::MutexGuard<'_, AppState>,
+    ui_to_network: &Sender<Message>,
+) {
+    let title = if state.recording {
+        "Session Recording (recording)"
+    } else {
+        "Session Recording"
+    };
+    CollapsingHeader::new(title)
+        .id_source("recording")
+        .default_open(false)
+        .show(ui, |ui| {
+            let mut to_send = None;
+            ui.horizontal(|ui| {
+                ui.label("Record to:");
+                ui.add_enabled(
+                    !state.recording,
+                    egui::TextEdit::singleline(&mut state.recording_path),
+                );
+                if state.recording {
+                    if ui.button("Stop").clicked() {
+                        state.recording = false;
+                        to_send = Some(Message::StopRecording);
+                    }
+                } else if ui
+                    .button("Record")
+                    .on_hover_text("Records every connection, both directions, into one file")
+                    .clicked()
+                {
+                    match Recorder::create(Path::new(&state.recording_path)) {
+                        Ok(recorder) => {
+                            state.recording = true;
+                            state.recording_error = None;
+                            to_send = Some(Message::StartRecording { recorder });
+                        }
+                        Err(e) => state.recording_error = Some(e.to_string()),
+                    }
+                }
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Replay:");
+                ui.text_edit_singleline(&mut state.replay_path);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Target:");
+                ui.text_edit_singleline(&mut state.replay_target)
+                    .on_hover_text("Leave empty to replay against the recorded URLs");
+            });
+            ui.horizontal(|ui| {
+                ui.label("Speed:");
+                ui.radio_value(&mut state.replay_speed, ReplaySpeed::Original, "Original");
+                if ui
+                    .radio(
+                        matches!(state.replay_speed, ReplaySpeed::Scaled(_)),
+                        "Scaled",
+                    )
+                    .clicked()
+                {
+                    state.replay_speed = ReplaySpeed::Scaled(2.);
+                }
+                if let ReplaySpeed::Scaled(factor) = &mut state.replay_speed {
+                    ui.add(
+                        egui::DragValue::new(factor)
+                            .speed(0.1)
+                            .clamp_range(0.1..=100.)
+                            .suffix("x"),
+                    );
+                }
+                ui.radio_value(
+                    &mut state.replay_speed,
+                    ReplaySpeed::AsFastAsPossible,
+                    "As fast as possible",
+                );
+            });
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(!state.replay_running, egui::Button::new("Replay"))
+                    .clicked()
+                {
+                    match load_recording(Path::new(&state.replay_path)) {
+                        Ok(events) => {
+                            state.replay_running = true;
+                            state.replay_report = None;
+                            state.recording_error = None;
+                            to_send = Some(Message::Replay {
+                                golden: None,
+                                events,
+                                target: replay_target(state),
+                                speed: state.replay_speed,
+                                ignore: Vec::new(),
+                            });
+                        }
+                        Err(e) => state.recording_error = Some(e.to_string()),
+                    }
+                }
+                if ui
+                    .button("Mark as Golden")
+                    .on_hover_text("Check servers against this recording's responses later")
+                    .clicked()
+                {
+                    let path = state.replay_path.clone();
+                    match load_recording(Path::new(&path)) {
+                        Ok(_) => {
+                            let name = Path::new(&path)
+                                .file_stem()
+                                .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+                            state.goldens.retain(|golden| golden.name != name);
+                            state.goldens.push(GoldenRecording {
+                                name,
+                                path,
+                                ..Default::default()
+                            });
+                            state.recording_error = None;
+                            if let Err(e) = store_app(state) {
+                                eprintln!("Could not store app state: {:?}", e);
+                            }
+                        }
+                        Err(e) => state.recording_error = Some(e.to_string()),
+                    }
+                }
+            });
+
+            if let Some(error) = &state.recording_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            if state.replay_running {
+                ui.label("Replaying...");
+            }
+            if let Some(report) = &state.replay_report {
+                render_replay_report(ui, report, "replay");
+            }
+
+            if let Some(message) = to_send {
+                let ui_to_network = ui_to_network.clone();
+                tokio::spawn(async move {
+                    let _ = ui_to_network.send(message).await;
+                });
+            }
+        });
+}
+
+fn replay_target(state: &AppState) -> Option<String> {
+    let target = state.replay_target.trim();
+    (!target.is_empty()).then(|| target.to_string())
+}
+
+fn render_replay_report(ui: &mut egui::Ui, report: &ReplayReport, id_source: &str) {
+    let summary = format!(
+        "{} connection(s), {} frames sent, {} compared, {} diff(s)",
+        report.connections.len(),
+        report.frames_sent,
+        report.frames_compared,
+        report.diffs.len()
+    );
+    if report.passed() {
+        ui.colored_label(egui::Color32::GREEN, summary);
+    } else {
+        ui.colored_label(egui::Color32::RED, summary);
+    }
+    egui::ScrollArea::vertical()
+        .id_source(("replay_diffs", id_source))
+        .max_height(300.)
+        .show(ui, |ui| {
+            for error in &report.errors {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            for diff in &report.diffs {
+                ui.label(format!("#{} frame {}", diff.conn, diff.index));
+                ui.add(
+                    egui::Label::new(format!(
+                        "  expected: {}",
+                        diff.expected.as_deref().unwrap_or("<nothing>")
+                    ))
+                    .wrap(true),
+                );
+                ui.add(
+                    egui::Label::new(format!(
+                        "  actual:   {}",
+                        diff.actual.as_deref().unwrap_or("<nothing>")
+                    ))
+                    .wrap(true),
+                );
+                for change in &diff.changes {
+                    ui.colored_label(egui::Color32::YELLOW, format!("    {}", change));
+                }
+            }
+        });
+}
+
+fn render_goldens(
+    ui: &mut egui::Ui,
+    state: &mut std::sync::MutexGuard<'_, AppState>,
+    ui_to_network: &Sender<Message>,
+) {
+    let state: &mut AppState = state;
+    CollapsingHeader::new(format!("Golden Recordings ({})", state.goldens.len()))
+        .id_source("goldens")
+        .default_open(false)
+        .show(ui, |ui| {
+            ui.label("Replayed against the Target and Speed of Session Recording.");
+            let mut to_check = Vec::new();
+            if !state.goldens.is_empty() && ui.button("Check All").clicked() {
+                to_check.extend(0..state.goldens.len());
+            }
+
+            let mut golden_to_remove = None;
+            let mut changed = false;
+            for index in 0..state.goldens.len() {
+                let name = state.goldens[index].name.clone();
+                let run = state.golden_runs.get(&name);
+                let icon = match (run, state.goldens[index].last_passed) {
+                    (Some(None), _) => "⏳",
+                    (_, Some(true)) => "✔",
+                    (_, Some(false)) => "❌",
+                    (_, None) => "·",
+                };
+                CollapsingHeader::new(format!("{} {}", icon, name))
+                    .id_source(("golden", &name))
+                    .show(ui, |ui| {
+                        let golden = &mut state.goldens[index];
+                        ui.label(format!("File: {}", golden.path));
+                        if let Some(last_run) = golden.last_run {
+                            ui.label(format!(
+                                "Last run: {}",
+                                last_run.format("%Y-%m-%d %H:%M:%S")
+                            ));
+                        }
+                        ui.label("Ignored fields:");
+                        let mut pointer_to_remove = None;
+                        for (pointer_index, pointer) in golden.ignore.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui.button("🗑").on_hover_text("Remove").clicked() {
+                                    pointer_to_remove = Some(pointer_index);
+                                }
+                                ui.label(pointer);
+                            });
+                        }
+                        if let Some(pointer_index) = pointer_to_remove {
+                            golden.ignore.remove(pointer_index);
+                            changed = true;
+                        }
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut golden.editing_ignore)
+                                .on_hover_text("JSON pointer, e.g. /timestamp or /items/*/id");
+                            if ui.button("Ignore").clicked() {
+                                match validate_pointer(&golden.editing_ignore) {
+                                    Ok(()) => {
+                                        let pointer = std::mem::take(&mut golden.editing_ignore);
+                                        golden.ignore.push(pointer);
+                                        changed = true;
+                                        state.golden_error = None;
+                                    }
+                                    Err(e) => state.golden_error = Some(e.to_string()),
+                                }
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            if ui
+                                .add_enabled(!matches!(run, Some(None)), egui::Button::new("Check"))
+                                .clicked()
+                            {
+                                to_check.push(index);
+                            }
+                            if ui.button("Remove").clicked() {
+                                golden_to_remove = Some(index);
+                            }
+                        });
+                        if let Some(Some(report)) = run {
+                            render_replay_report(ui, report, &name);
+                        }
+                    });
+            }
+            if let Some(error) = &state.golden_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+
+            for index in to_check {
+                let golden = state.goldens[index].clone();
+                match load_recording(Path::new(&golden.path)) {
+                    Ok(events) => {
+                        let message = Message::Replay {
+                            golden: Some(golden.name.clone()),
+                            events,
+                            target: replay_target(state),
+                            speed: state.replay_speed,
+                            ignore: golden.ignore,
+                        };
+                        state.golden_runs.insert(golden.name, None);
+                        let ui_to_network = ui_to_network.clone();
+                        tokio::spawn(async move {
+                            let _ = ui_to_network.send(message).await;
+                        });
+                    }
+                    Err(e) => state.golden_error = Some(format!("{}: {}", golden.name, e)),
+                }
+            }
+            if let Some(index) = golden_to_remove {
+                let golden = state.goldens.remove(index);
+                state.golden_runs.remove(&golden.name);
+                changed = true;
+            }
+            if changed {
+                if let Err(e) = store_app(state) {
+                    eprintln!("Could not store app state: {:?}", e);
+                }
+            }
+        });
+}
+
+fn render_messages(ui: &mut egui::Ui, state: &mut AppState, window_index: usize) {
+    let window = &mut state.connections[window_index];
+    let messages = &window.connection.messages;
+    let first_recent = messages.first_recent();
+    let total = messages.len();
+
+    let mut page_start = None;
+    let mut follow = false;
+    ui.horizontal(|ui| {
+        ui.label("Messages:");
+        ui.radio_value(&mut window.message_view, MessageView::Raw, "Raw");
+        ui.radio_value(&mut window.message_view, MessageView::Pretty, "Pretty")
+            .on_hover_text("Pretty-prints JSON messages");
+        ui.radio_value(&mut window.message_view, MessageView::Tree, "Tree")
+            .on_hover_text("Shows JSON messages as a tree, right-click a node to copy it");
+        ui.separator();
+        match &window.history_page {
+            Some(page) => {
+                ui.label(format!(
+                    "{}-{} of {}",
+                    page.start + 1,
+                    page.start + page.entries.len(),
+                    total
+                ));
+                if ui
+                    .add_enabled(page.start > 0, egui::Button::new("◀ Older"))
+                    .clicked()
+                {
+                    page_start = Some(page.start.saturating_sub(HISTORY_PAGE_SIZE));
+                }
+                if ui.button("Newer ▶").clicked() {
+                    let start = page.start + HISTORY_PAGE_SIZE;
+                    if start >= first_recent {
+                        follow = true;
+                    } else {
+                        page_start = Some(start);
+                    }
+                }
+                if ui.button("Latest").clicked() {
+                    follow = true;
+                }
+            }
+            None => {
+                ui.label(format!("last {} of {}", total - first_recent, total));
+                if ui
+                    .add_enabled(first_recent > 0, egui::Button::new("◀ Older"))
+                    .on_hover_text("Older messages are read back from the history file")
+                    .clicked()
+                {
+                    page_start = Some(first_recent.saturating_sub(HISTORY_PAGE_SIZE));
+                }
+            }
+        }
+    });
+    if let Some(start) = page_start {
+        // Pages never overlap the in-memory messages, those are shown when following
+        let count = HISTORY_PAGE_SIZE.min(first_recent - start);
+        window.history_page = Some(HistoryPage {
+            start,
+            entries: messages.page(start, count),
+        });
+    } else if follow {
+        window.history_page = None;
+    }
+
+    let window = &mut state.connections[window_index];
+    render_filter_bar(ui, &mut window.filter, ("filter", window.id));
+    window.filter_cache.update(&window.filter);
+    if let Some(error) = window.filter_cache.error() {
+        ui.colored_label(egui::Color32::RED, error);
+    }
+
+    let cache = &mut window.filter_cache;
+    let (first_shown, entries): (usize, Box<dyn Iterator<Item = &LogEntry>>) =
+        match &window.history_page {
+            Some(page) => (page.start, Box::new(page.entries.iter())),
+            None => (first_recent, Box::new(window.connection.messages.recent())),
+        };
+    let entries: Vec<(usize, &LogEntry)> = entries
+        .enumerate()
+        .map(|(index, entry)| (first_shown + index, entry))
+        .filter(|(index, entry)| cache.matches(*index, entry))
+        .collect();
+    if window.message_view != MessageView::Raw {
+        window.json_cache.update(entries.iter().copied());
+    }
+    let window = &*window;
+    let show_inspector = window.inspected.is_some() || !window.compared.is_empty();
+    let filter = window.filter_cache.compiled();
+    if filter.is_some() {
+        ui.label(format!("{} matching", entries.len()));
+    }
+
+    let mut action = None;
+    if show_inspector {
+        ui.columns(2, |columns| {
+            action = render_message_list(&mut columns[0], window, &entries, filter)
+                .or(render_inspector(&mut columns[1], window));
+        });
+    } else {
+        action = render_message_list(ui, window, &entries, filter);
+    }
+
+    let window = &mut state.connections[window_index];
+    match action {
+        Some(InspectorAction::Inspect(index, entry)) => {
+            if window.inspected.as_ref().is_some_and(|(i, _)| *i == index) {
+                window.inspected = None;
+            } else {
+                window.inspected = Some((index, entry));
+            }
+        }
+        Some(InspectorAction::Compare(index, entry)) => {
+            if window.compared.iter().any(|(i, _)| *i == index) {
+                window.compared.retain(|(i, _)| *i != index);
+            } else {
+                window.compared.push((index, entry));
+                if window.compared.len() > 2 {
+                    window.compared.remove(0);
+                }
+            }
+            window.diff = compare(&window.compared);
+        }
+        Some(InspectorAction::ClearComparison) => {
+            window.compared.clear();
+            window.diff = None;
+        }
+        Some(InspectorAction::Close) => {
+            window.inspected = None;
+            window.compared.clear();
+            window.diff = None;
+        }
+        None => {}
+    }
+}
+
+/// Changes to the message inspector made from the message list or the inspector itself.
+enum InspectorAction {
+    Inspect(usize, LogEntry),
+    Compare(usize, LogEntry),
+    ClearComparison,
+    Close,
+}
+
+/// The diff of the two `compared` entries, from the older to the newer one.
+fn compare(compared: &[(usize, LogEntry)]) -> Option<Diff> {
+    let [first, second] = compared else {
+        return None;
+    };
+    let (old, new) = if first.0 < second.0 {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    Some(diff(&old.1.text, &new.1.text))
+}
+
+fn render_message_list(
+    ui: &mut egui::Ui,
+    window: &ConnectionWindow,
+    entries: &[(usize, &LogEntry)],
+    filter: Option<&CompiledFilter>,
+) -> Option<InspectorAction> {
+    let mut action = None;
+    egui::ScrollArea::vertical()
+        .id_source(("messages", window.id))
+        .min_scrolled_height(400.)
+        .stick_to_bottom(window.history_page.is_none())
+        .show(ui, |ui| {
+            ui.vertical(|ui| {
+                let mut previous_us = None;
+                for &(index, entry) in entries {
+                    ui.horizontal(|ui| {
+                        if ui.button("📋").on_hover_text("Click to copy").clicked() {
+                            ui.output_mut(|o| o.copied_text = entry.text.clone());
+                        }
+                        let inspected = window.inspected.as_ref().is_some_and(|(i, _)| *i == index);
+                        if ui
+                            .selectable_label(inspected, "🔍")
+                            .on_hover_text("Inspect")
+                            .clicked()
+                        {
+                            action = Some(InspectorAction::Inspect(index, entry.clone()));
+                        }
+                        let compared = window.compared.iter().any(|(i, _)| *i == index);
+                        if ui
+                            .selectable_label(compared, "⇄")
+                            .on_hover_text("Select two messages to compare them")
+                            .clicked()
+                        {
+                            action = Some(InspectorAction::Compare(index, entry.clone()));
+                        }
+                        let id = egui::Id::new(("message", window.id, index));
+                        let json = window.json_cache.get(index);
+                        render_log_entry(
+                            ui,
+                            entry,
+                            previous_us,
+                            window.message_view,
+                            json,
+                            filter,
+                            id,
+                        );
+                    });
+                    previous_us = Some(entry.elapsed_us);
+                }
+            });
+        });
+    action
+}
+
+/// Details of the inspected message and the diff of the messages selected for comparison.
+fn render_inspector(ui: &mut egui::Ui, window: &ConnectionWindow) -> Option<InspectorAction> {
+    let mut action = None;
+    ui.horizontal(|ui| {
+        ui.strong("Inspector");
+        if ui.button("✖").on_hover_text("Close").clicked() {
+            action = Some(InspectorAction::Close);
+        }
+    });
+    egui::ScrollArea::vertical()
+        .id_source(("inspector", window.id))
+        .max_height(600.)
+        .show(ui, |ui| {
+            if let Some((index, entry)) = &window.inspected {
+                render_entry_details(ui, window.id, *index, entry);
+            }
+            if !window.compared.is_empty() {
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.strong("Diff");
+                    if ui.button("Clear").clicked() {
+                        action = Some(InspectorAction::ClearComparison);
+                    }
+                });
+                match (&window.diff, window.compared.as_slice()) {
+                    (Some(diff), [first, second]) => {
+                        ui.label(format!(
+                            "#{} → #{}",
+                            first.0.min(second.0) + 1,
+                            first.0.max(second.0) + 1
+                        ));
+                        render_diff(ui, diff);
+                    }
+                    (_, compared) => {
+                        ui.label(format!(
+                            "Select another message with ⇄ to compare it with #{}",
+                            compared[0].0 + 1
+                        ));
+                    }
+                }
+            }
+        });
+    action
+}
+
+fn render_entry_details(ui: &mut egui::Ui, window_id: u8, index: usize, entry: &LogEntry) {
+    egui::Grid::new(("inspector_details", window_id))
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Message:");
+            ui.label(format!("#{}", index + 1));
+            ui.end_row();
+            ui.label("Direction:");
+            ui.label(format!("{:?}", entry.direction));
+            ui.end_row();
+            if let Some(opcode) = entry.opcode {
+                ui.label("Frame:");
+                ui.label(format!("{:?}", opcode));
+                ui.end_row();
+            }
+            ui.label("Size:");
+            ui.label(format!("{} bytes", entry.size));
+            ui.end_row();
+            if let Some(codec) = &entry.codec {
+                ui.label("Decoded:");
+                ui.label(codec);
+                ui.end_row();
+            }
+            ui.label("Time:");
+            ui.label(
+                entry
+                    .timestamp
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M:%S%.6f")
+                    .to_string(),
+            );
+            ui.end_row();
+            ui.label("Since start:");
+            ui.label(format!("{:.6} s", entry.elapsed_us as f64 / 1_000_000.));
+            ui.end_row();
+        });
+
+    let id = egui::Id::new(("inspector", window_id, index));
+    CollapsingHeader::new("Payload")
+        .id_source(id.with("payload"))
+        .default_open(true)
+        .show(ui, |ui| {
+            if ui.button("📋 Copy").clicked() {
+                ui.output_mut(|o| o.copied_text = entry.text.clone());
+            }
+            ui.add(
+                egui::TextEdit::multiline(&mut entry.text.as_str())
+                    .code_editor()
+                    .desired_width(f32::INFINITY),
+            );
+        });
+    if let Some(value) = parse_json(&entry.text) {
+        CollapsingHeader::new("JSON")
+            .id_source(id.with("json"))
+            .show(ui, |ui| {
+                let pretty = serde_json::to_string_pretty(&value).unwrap_or_default();
+                ui.add(egui::Label::new(json_layout(&pretty)).wrap(true));
+            });
+        CollapsingHeader::new("JSON Tree")
+            .id_source(id.with("tree"))
+            .show(ui, |ui| render_json_tree(ui, &value, "$", "", id));
+    }
+    CollapsingHeader::new("Hex")
+        .id_source(id.with("hex"))
+        .show(ui, |ui| {
+            // Binary frames keep their bytes, the bytes of other entries are their text
+            let data = if entry.data.is_empty() {
+                entry.text.as_bytes()
+            } else {
+                &entry.data
+            };
+            egui::ScrollArea::horizontal()
+                .id_source(id.with("hex_scroll"))
+                .show(ui, |ui| {
+                    ui.add(
+                        egui::Label::new(
+                            egui::RichText::new(hex_dump(data, &environment::secret_ranges(data)))
+                                .monospace(),
+                        )
+                        .wrap(false),
+                    );
+                });
+        });
+}
+
+fn render_diff(ui: &mut egui::Ui, diff: &Diff) {
+    let line = |ui: &mut egui::Ui, color: egui::Color32, text: String| {
+        ui.add(egui::Label::new(egui::RichText::new(text).monospace().color(color)).wrap(true));
+    };
+    match diff {
+        Diff::Json(changes) if changes.is_empty() => {
+            ui.label("No differences");
+        }
+        Diff::Json(changes) => {
+            for change in changes {
+                match &change.kind {
+                    ChangeKind::Added(value) => line(
+                        ui,
+                        DIFF_ADDED_COLOR,
+                        format!("+ {}: {}", change.path, value),
+                    ),
+                    ChangeKind::Removed(value) => line(
+                        ui,
+                        DIFF_REMOVED_COLOR,
+                        format!("- {}: {}", change.path, value),
+                    ),
+                    ChangeKind::Changed(old, new) => line(
+                        ui,
+                        DIFF_CHANGED_COLOR,
+                        format!("~ {}: {} → {}", change.path, old, new),
+                    ),
+                }
+            }
+        }
+        Diff::Lines(lines) if lines.iter().all(|l| matches!(l, DiffLine::Same(_))) => {
+            ui.label("No differences");
+        }
+        Diff::Lines(lines) => {
+            for diff_line in lines {
+                match diff_line {
+                    DiffLine::Same(text) => line(ui, egui::Color32::GRAY, format!("  {}", text)),
+                    DiffLine::Removed(text) => line(ui, DIFF_REMOVED_COLOR, format!("- {}", text)),
+                    DiffLine::Added(text) => line(ui, DIFF_ADDED_COLOR, format!("+ {}", text)),
+                }
+            }
+        }
+    }
+}
+
+fn render_filter_bar(
+    ui: &mut egui::Ui,
+    filter: &mut MessageFilter,
+    id_source: impl std::hash::Hash,
+) {
+    ui.push_id(id_source, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            egui::ComboBox::from_id_source("kind")
+                .width(80.)
+                .selected_text(match filter.kind {
+                    FilterKind::Text => "Text",
+                    FilterKind::Regex => "Regex",
+                    FilterKind::JsonPath => "JSONPath",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut filter.kind, FilterKind::Text, "Text");
+                    ui.selectable_value(&mut filter.kind, FilterKind::Regex, "Regex");
+                    ui.selectable_value(&mut filter.kind, FilterKind::JsonPath, "JSONPath")
+                        .on_hover_text("Matches JSON messages the query selects a node of");
+                });
+            let hint = match filter.kind {
+                FilterKind::Text => "contains, ignoring case",
+                FilterKind::Regex => "error|fail",
+                FilterKind::JsonPath => "$[?@.type == 'error']",
+            };
+            ui.add(
+                egui::TextEdit::singleline(&mut filter.query)
+                    .hint_text(hint)
+                    .desired_width(180.),
+            );
+            ui.radio_value(&mut filter.direction, None, "Both");
+            ui.radio_value(&mut filter.direction, Some(Direction::Sent), "→ Sent");
+            ui.radio_value(
+                &mut filter.direction,
+                Some(Direction::Received),
+                "← Received",
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("From:");
+            ui.add(
+                egui::TextEdit::singleline(&mut filter.from)
+                    .hint_text("HH:MM[:SS]")
+                    .desired_width(130.),
+            );
+            ui.label("To:");
+            ui.add(
+                egui::TextEdit::singleline(&mut filter.to)
+                    .hint_text("YYYY-MM-DD HH:MM")
+                    .desired_width(130.),
+            );
+            if ui.button("Clear").clicked() {
+                *filter = MessageFilter::default();
+            }
+        });
+    });
+}
+
+/// Table of every connection with its status and traffic, to act on several at once and find
+/// their windows.
+fn render_overview(ctx: &Context, state: &mut AppState, ui_to_network: &Sender<Message>) {
+    let mut open = state.show_overview;
+    egui::Window::new("Connections Overview")
+        .open(&mut open)
+        .resizable(true)
+        .show(ctx, |ui| {
+            if state.connections.is_empty() {
+                ui.label("No connections");
+                return;
+            }
+            state
+                .overview_selection
+                .retain(|id| state.connections.iter().any(|w| w.id == *id));
+            let selected = state.overview_selection.len();
+            let mut bulk = None;
+            ui.horizontal(|ui| {
+                ui.label(format!("{} selected:", selected));
+                for (action, label) in [
+                    (BulkAction::Connect, "Connect"),
+                    (BulkAction::Disconnect, "Disconnect"),
+                    (BulkAction::Ping, "Ping"),
+                    (BulkAction::StartJob, "Start Job"),
+                    (BulkAction::StopJob, "Stop Job"),
+                    (BulkAction::Close, "Close"),
+                ] {
+                    if ui
+                        .add_enabled(selected > 0, egui::Button::new(label))
+                        .clicked()
+                    {
+                        bulk = Some(action);
+                    }
+                }
+            });
+            if let Some(action) = bulk {
+                apply_bulk_action(state, action, ui_to_network);
+            }
+
+            let mut order: Vec<&ConnectionWindow> = state.connections.iter().collect();
+            order.sort_by(|a, b| {
+                let uptime =
+                    |w: &ConnectionWindow| w.connection.connected_at.map(|at| at.elapsed());
+                let last_frame =
+                    |w: &ConnectionWindow| w.connection.messages.last_frame().map(|e| e.timestamp);
+                let (ca, cb) = (&a.connection, &b.connection);
+                let ordering = match state.overview_sort {
+                    OverviewColumn::Id => a.id.cmp(&b.id),
+                    OverviewColumn::Status => ca.is_connected.cmp(&cb.is_connected),
+                    OverviewColumn::Url => ca.url.cmp(&cb.url),
+                    OverviewColumn::Uptime => uptime(a).cmp(&uptime(b)),
+                    OverviewColumn::SentBytes => ca.send_bytes.cmp(&cb.send_bytes),
+                    OverviewColumn::ReceivedBytes => ca.received_bytes.cmp(&cb.received_bytes),
+                    OverviewColumn::Messages => ca.messages.len().cmp(&cb.messages.len()),
+                    OverviewColumn::LastMessage => last_frame(a).cmp(&last_frame(b)),
+                    OverviewColumn::Rtt => ca.last_rtt.cmp(&cb.last_rtt),
+                };
+                if state.overview_descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+
+            let mut sort_by = None;
+            let mut select_all = None;
+            let mut toggled = None;
+            let mut focus = None;
+            egui::ScrollArea::both().show(ui, |ui| {
+                egui::Grid::new("overview")
+                    .striped(true)
+                    .num_columns(12)
+                    .show(ui, |ui| {
+                        let mut all = selected == state.connections.len();
+                        if ui.checkbox(&mut all, "").changed() {
+                            select_all = Some(all);
+                        }
+                        for (column, title) in [
+                            (OverviewColumn::Id, "Connection"),
+                            (OverviewColumn::Status, "Status"),
+                            (OverviewColumn::Url, "URL"),
+                            (OverviewColumn::Uptime, "Uptime"),
+                            (OverviewColumn::SentBytes, "Sent"),
+                            (OverviewColumn::ReceivedBytes, "Received"),
+                            (OverviewColumn::Messages, "Messages"),
+                            (OverviewColumn::LastMessage, "Last Message"),
+                            (OverviewColumn::Rtt, "RTT"),
+                        ] {
+                            let sorted = state.overview_sort == column;
+                            let title = match (sorted, state.overview_descending) {
+                                (true, false) => format!("{} ⏶", title),
+                                (true, true) => format!("{} ⏷", title),
+                                (false, _) => title.to_string(),
+                            };
+                            if ui.selectable_label(sorted, title).clicked() {
+                                sort_by = Some(column);
+                            }
+                        }
+                        ui.strong("Job");
+                        ui.label("");
+                        ui.end_row();
+
+                        for window in order {
+                            let connection = &window.connection;
+                            let mut checked = state.overview_selection.contains(&window.id);
+                            if ui.checkbox(&mut checked, "").changed() {
+                                toggled = Some((window.id, checked));
+                            }
+                            ui.label(window.title());
+                            if connection.is_connected {
+                                ui.colored_label(
+                                    egui::Color32::from_rgb(90, 200, 120),
+                                    "connected",
+                                );
+                            } else {
+                                ui.colored_label(egui::Color32::GRAY, "disconnected");
+                            }
+                            ui.label(&connection.url);
+                            ui.label(
+                                connection
+                                    .connected_at
+                                    .map_or(String::from("-"), |at| format_duration(at.elapsed())),
+                            );
+                            ui.label(format!("{} B", connection.send_bytes));
+                            ui.label(format!("{} B", connection.received_bytes));
+                            let (sent, received) = connection.messages.frame_counts();
+                            ui.label(format!("{} / {}", sent, received))
+                                .on_hover_text("Frames sent / received");
+                            match connection.messages.last_frame() {
+                                Some(entry) => {
+                                    let age = (chrono::Utc::now() - entry.timestamp)
+                                        .to_std()
+                                        .unwrap_or_default();
+                                    ui.label(format!("{} ago", format_duration(age)))
+                                        .on_hover_text(
+                                            entry
+                                                .timestamp
+                                                .with_timezone(&chrono::Local)
+                                                .format("%Y-%m-%d %H:%M:%S")
+                                                .to_string(),
+                                        );
+                                }
+                                None => {
+                                    ui.label("-");
+                                }
+                            }
+                            ui.label(connection.last_rtt.map_or(String::from("-"), |rtt| {
+                                format!("{} ms", rtt.as_millis())
+                            }));
+                            ui.label(active_jobs(window));
+                            if ui
+                                .button("Show")
+                                .on_hover_text("Shows the connection's tab")
+                                .clicked()
+                            {
+                                focus = Some(window.id);
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+            if let Some(column) = sort_by {
+                state.overview_descending =
+                    state.overview_sort == column && !state.overview_descending;
+                state.overview_sort = column;
+            }
+            match select_all {
+                Some(true) => {
+                    state.overview_selection = state.connections.iter().map(|w| w.id).collect()
+                }
+                Some(false) => state.overview_selection.clear(),
+                None => {}
+            }
+            match toggled {
+                Some((id, true)) => {
+                    state.overview_selection.insert(id);
+                }
+                Some((id, false)) => {
+                    state.overview_selection.remove(&id);
+                }
+                None => {}
+            }
+            if let Some(id) = focus {
+                state.layout.focus(id);
+            }
+        });
+    state.show_overview = open;
+}
+
+#[derive(Clone, Copy)]
+enum BulkAction {
+    Connect,
+    Disconnect,
+    Ping,
+    StartJob,
+    StopJob,
+    Close,
+}
+
+fn apply_bulk_action(state: &mut AppState, action: BulkAction, ui_to_network: &Sender<Message>) {
+    let mut messages = Vec::new();
+    for window in state
+        .connections
+        .iter_mut()
+        .filter(|w| state.overview_selection.contains(&w.id))
+    {
+        let connection = &mut window.connection;
+        match action {
+            BulkAction::Connect if !connection.is_connected => {
+                messages.push(Message::NewClient {
+                    id: window.id,
+                    ip: render_url(&connection.url),
+                });
+            }
+            BulkAction::Disconnect | BulkAction::Close if connection.is_connected => {
+                // Closed by us, not by the last message
+                connection.last_sent = None;
+                messages.push(Message::Close {
+                    id: window.id,
+                    code: None,
+                    reason: String::new(),
+                });
+            }
+            BulkAction::Ping if connection.is_connected => {
+                connection.messages.push_control(
+                    Direction::Sent,
+                    Opcode::Ping,
+                    String::from("ping"),
+                );
+                messages.push(Message::Ping {
+                    id: window.id,
+                    payload: Vec::new(),
+                });
+            }
+            BulkAction::StartJob if !connection.job_running => start_job(window),
+            BulkAction::StopJob => connection.job_running = false,
+            _ => {}
+        }
+        if let BulkAction::Close = action {
+            state.windows_to_remove.push(window.id);
+        }
+    }
+    let ui_to_network = ui_to_network.clone();
+    tokio::spawn(async move {
+        for message in messages {
+            let _ = ui_to_network.send(message).await;
+        }
+    });
+}
+
+/// What runs on a connection besides manual sends, e.g. `periodically, script`.
+fn active_jobs(window: &ConnectionWindow) -> String {
+    let mut jobs = Vec::new();
+    if window.connection.job_running {
+        jobs.push(format!("{:?}", window.send_option).to_lowercase());
+    }
+    if let Some(run) = window.scenario_run.as_ref().filter(|r| r.passed.is_none()) {
+        jobs.push(format!("scenario {}", run.name));
+    }
+    if window.connection.script_attached {
+        jobs.push(String::from("script"));
+    }
+    if jobs.is_empty() {
+        String::from("-")
+    } else {
+        jobs.join(", ")
+    }
+}
+
+/// `1h 02m`, `3m 05s` or `12s`.
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+/// Composer sending one template to several connections, expanded for each of them.
+fn render_broadcast(
+    ui: &mut egui::Ui,
+    state: &mut std::sync::MutexGuard<'_, AppState>,
+    ui_to_network: &Sender<Message>,
+) {
+    let state: &mut AppState = state;
+    CollapsingHeader::new("Broadcast")
+        .id_source("broadcast")
+        .show(ui, |ui| {
+            if state.connections.is_empty() {
+                ui.label("No connections");
+                return;
+            }
+            let mut groups: Vec<&str> = state
+                .connections
+                .iter()
+                .map(|window| window.group.as_str())
+                .filter(|group| !group.is_empty())
+                .collect();
+            groups.sort_unstable();
+            groups.dedup();
+            let mut selection = None;
+            ui.horizontal_wrapped(|ui| {
+                ui.label("Select:");
+                if ui.button("All").clicked() {
+                    selection = Some(state.connections.iter().map(|w| w.id).collect());
+                }
+                if ui.button("None").clicked() {
+                    selection = Some(BTreeSet::new());
+                }
+                for group in groups {
+                    if ui.button(format!("Group {}", group)).clicked() {
+                        selection = Some(
+                            state
+                                .connections
+                                .iter()
+                                .filter(|w| w.group == group)
+                                .map(|w| w.id)
+                                .collect(),
+                        );
+                    }
+                }
+            });
+            if let Some(selection) = selection {
+                state.broadcast_targets = selection;
+            }
+
+            for window in &state.connections {
+                ui.horizontal(|ui| {
+                    let mut selected = state.broadcast_targets.contains(&window.id);
+                    let label = format!("{} {}", window.title(), window.connection.url);
+                    if ui.checkbox(&mut selected, label).changed() {
+                        if selected {
+                            state.broadcast_targets.insert(window.id);
+                        } else {
+                            state.broadcast_targets.remove(&window.id);
+                        }
+                    }
+                    if !window.group.is_empty() {
+                        ui.label(egui::RichText::new(&window.group).weak());
+                    }
+                    if !window.connection.is_connected {
+                        ui.colored_label(egui::Color32::GRAY, "(disconnected)");
+                    }
+                });
+            }
+
+            ui.add(
+                egui::TextEdit::multiline(&mut state.broadcast_message)
+                    .hint_text("Expanded for each connection, e.g. {{conn_id}}")
+                    .desired_rows(3),
+            )
+            .on_hover_text(TEMPLATE_HINT);
+            let targets: Vec<usize> = state
+                .connections
+                .iter()
+                .enumerate()
+                .filter(|(_, window)| state.broadcast_targets.contains(&window.id))
+                .map(|(index, _)| index)
+                .collect();
+            let send = ui
+                .add_enabled(
+                    !targets.is_empty() && !state.broadcast_message.is_empty(),
+                    egui::Button::new(format!("Send to {} connections", targets.len())),
+                )
+                .clicked();
+            if send {
+                let template = state.broadcast_message.clone();
+                let mut actions = Vec::new();
+                for window_index in targets {
+                    send_template(
+                        state,
+                        window_index,
+                        &template,
+                        &mut actions,
+                        ui_to_network.clone(),
+                    );
+                }
+                apply_actions(state, actions);
+            }
+        });
+}
+
+fn render_search(
+    ui: &mut egui::Ui,
+    state: &mut std::sync::MutexGuard<'_, AppState>,
+    ui_to_network: &Sender<Message>,
+) {
+    let state: &mut AppState = state;
+    CollapsingHeader::new("Search All Connections")
+        .id_source("search")
+        .show(ui, |ui| {
+            render_filter_bar(ui, &mut state.search, "search");
+            if ui
+                .add_enabled(!state.search_running, egui::Button::new("Search"))
+                .on_hover_text("Searches the whole history of every connection")
+                .clicked()
+            {
+                state.search_error = None;
+                match CompiledFilter::new(&state.search) {
+                    Ok(filter) => {
+                        let filter = Arc::new(filter);
+                        let histories = state
+                            .connections
+                            .iter()
+                            .map(|window| (window.id, window.connection.messages.search()))
+                            .collect();
+                        state.search_running = true;
+                        state.search_filter = Some(filter.clone());
+                        let ui_to_network = ui_to_network.clone();
+                        tokio::spawn(async move {
+                            let _ = ui_to_network
+                                .send(Message::Search { filter, histories })
+                                .await;
+                        });
+                    }
+                    Err(e) => state.search_error = Some(e.to_string()),
+                }
+            }
+            if state.search_running {
+                ui.label("Searching...");
+            }
+            if let Some(error) = &state.search_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+
+            let mut to_show = None;
+            let highlight = state.search_filter.as_deref();
+            egui::ScrollArea::vertical()
+                .id_source("search_results")
+                .max_height(300.)
+                .show(ui, |ui| {
+                    for hit in &state.search_results {
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button("Show")
+                                .on_hover_text(
+                                    "Shows the message in its connection tab and inspects it",
+                                )
+                                .clicked()
+                            {
+                                to_show = Some((hit.conn, hit.index, hit.entry.clone()));
+                            }
+                            let title = state
+                                .connections
+                                .iter()
+                                .find(|w| w.id == hit.conn)
+                                .map_or_else(|| format!("#{}", hit.conn), |w| w.title());
+                            ui.label(format!(
+                                "{} {}",
+                                title,
+                                hit.entry
+                                    .timestamp
+                                    .with_timezone(&chrono::Local)
+                                    .format("%H:%M:%S%.3f")
+                            ));
+                            render_log_entry(
+                                ui,
+                                &hit.entry,
+                                None,
+                                MessageView::Raw,
+                                None,
+                                highlight,
+                                egui::Id::new(("search_hit", hit.conn, hit.index)),
+                            );
+                        });
+                    }
+                });
+            if let Some((conn, index, entry)) = to_show {
+                if let Some(window) = state.connections.iter_mut().find(|w| w.id == conn) {
+                    let messages = &window.connection.messages;
+                    let first_recent = messages.first_recent();
+                    window.history_page = (index < first_recent).then(|| HistoryPage {
+                        start: index,
+                        entries: messages.page(index, HISTORY_PAGE_SIZE.min(first_recent - index)),
+                    });
+                    window.inspected = Some((index, entry));
+                    state.layout.focus(conn);
+                }
+            }
+        });
+}
+
+/// An entry of the message list: direction arrow, time since the previous entry, frame type
+/// and payload. The wall-clock time and size are shown on hover. `json` is the parsed payload
+/// shown by the Pretty and Tree views.
+fn render_log_entry(
+    ui: &mut egui::Ui,
+    entry: &LogEntry,
+    previous_us: Option<u64>,
+    view: MessageView,
+    json: Option<&ParsedJson>,
+    filter: Option<&CompiledFilter>,
+    id: egui::Id,
+) {
+    let (arrow, color) = match entry.direction {
+        Direction::Sent => ("→", egui::Color32::from_rgb(90, 160, 255)),
+        Direction::Received => ("←", egui::Color32::from_rgb(90, 200, 120)),
+        Direction::Info => ("•", egui::Color32::GRAY),
+    };
+    ui.colored_label(color, arrow);
+    // Entries read back from disk may come from an earlier log, so never show a negative delta
+    let delta_us = previous_us.map_or(0, |previous| entry.elapsed_us.saturating_sub(previous));
+    ui.add(
+        egui::Label::new(
+            egui::RichText::new(format!("+{:>7.1} ms", delta_us as f64 / 1000.))
+                .monospace()
+                .weak(),
+        )
+        .wrap(false),
+    )
+    .on_hover_text(format!(
+        "{}\n{:.3} s since the log started",
+        entry
+            .timestamp
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M:%S%.3f"),
+        entry.elapsed_us as f64 / 1_000_000.
+    ));
+    if let Some(opcode) = entry.opcode {
+        let tag = match opcode {
+            Opcode::Text => "text",
+            Opcode::Binary => "binary",
+            Opcode::Ping => "ping",
+            Opcode::Pong => "pong",
+            Opcode::Close => "close",
+        };
+        let tag = match &entry.codec {
+            Some(codec) => format!("{} {}", tag, codec),
+            None => tag.to_string(),
+        };
+        ui.label(egui::RichText::new(tag).small().weak())
+            .on_hover_text(format!("{} bytes", entry.size));
+    }
+    match (view, json) {
+        (MessageView::Pretty, Some(json)) => {
+            ui.add(egui::Label::new(json_layout(&json.pretty)).wrap(true));
+        }
+        (MessageView::Tree, Some(json)) => {
+            ui.vertical(|ui| render_json_tree(ui, &json.value, "$", "", id));
+        }
+        _ => {
+            let highlights = filter
+                .map(|f| f.highlights(&entry.text))
+                .unwrap_or_default();
+            if !highlights.is_empty() {
+                ui.add(egui::Label::new(highlighted(ui, &entry.text, &highlights)).wrap(true));
+                return;
+            }
+            let text = egui::RichText::new(&entry.text);
+            let text = if entry.direction == Direction::Info {
+                text.italics().color(egui::Color32::GRAY)
+            } else {
+                text
+            };
+            ui.add(egui::Label::new(text).wrap(true));
+        }
+    }
+}
+
+/// `text` with the `ranges` of a filter match highlighted.
+fn highlighted(ui: &egui::Ui, text: &str, ranges: &[std::ops::Range<usize>]) -> LayoutJob {
+    let font = egui::TextStyle::Body.resolve(ui.style());
+    let normal = egui::TextFormat::simple(font, ui.visuals().text_color());
+    let highlight = egui::TextFormat {
+        background: ui.visuals().selection.bg_fill,
+        color: ui.visuals().strong_text_color(),
+        ..normal.clone()
+    };
+    let mut job = LayoutJob::default();
+    let mut end = 0;
+    for range in ranges {
+        job.append(&text[end..range.start], 0., normal.clone());
+        job.append(&text[range.clone()], 0., highlight.clone());
+        end = range.end;
+    }
+    job.append(&text[end..], 0., normal);
+    job
+}
+
+const JSON_KEY_COLOR: egui::Color32 = egui::Color32::from_rgb(120, 170, 230);
+const JSON_STRING_COLOR: egui::Color32 = egui::Color32::from_rgb(210, 160, 100);
+const JSON_NUMBER_COLOR: egui::Color32 = egui::Color32::from_rgb(150, 200, 130);
+const JSON_LITERAL_COLOR: egui::Color32 = egui::Color32::from_rgb(190, 130, 210);
+const DIFF_ADDED_COLOR: egui::Color32 = egui::Color32::from_rgb(110, 200, 120);
+const DIFF_REMOVED_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 110, 110);
+const DIFF_CHANGED_COLOR: egui::Color32 = egui::Color32::from_rgb(220, 190, 90);
+
+/// Syntax-coloured layout of a JSON document.
+fn json_layout(json: &str) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    let font = egui::FontId::monospace(12.);
+    let mut chars = json.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        let color = match c {
+            '"' => {
+                let mut escaped = false;
+                for (i, c) in chars.by_ref() {
+                    end = i + c.len_utf8();
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => break,
+                        _ => escaped = false,
+                    }
+                }
+                // A string followed by a colon is a key
+                let rest = json[end..].trim_start();
+                if rest.starts_with(':') {
+                    JSON_KEY_COLOR
+                } else {
+                    JSON_STRING_COLOR
+                }
+            }
+            '-' | '0'..='9' => {
+                while let Some((i, c)) = chars.next_if(|(_, c)| {
+                    c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-')
+                }) {
+                    end = i + c.len_utf8();
+                }
+                JSON_NUMBER_COLOR
+            }
+            'a'..='z' => {
+                while let Some((i, c)) = chars.next_if(|(_, c)| c.is_ascii_alphabetic()) {
+                    end = i + c.len_utf8();
+                }
+                JSON_LITERAL_COLOR
+            }
+            _ => egui::Color32::GRAY,
+        };
+        job.append(
+            &json[start..end],
+            0.,
+            egui::TextFormat::simple(font.clone(), color),
+        );
+    }
+    job
+}
+
+/// Shows `value` as an expandable tree. `path` is its JSONPath, as used by extraction rules.
+fn render_json_tree(ui: &mut egui::Ui, value: &Value, path: &str, key: &str, id: egui::Id) {
+    let children: Vec<(String, String, &Value)> = match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, child)| (format!("{}: ", key), json_path_key(path, key), child))
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(index, child)| {
+                (
+                    format!("{}: ", index),
+                    format!("{}[{}]", path, index),
+                    child,
+                )
+            })
+            .collect(),
+        _ => {
+            let (text, color) = match value {
+                Value::String(text) => (format!("{:?}", text), JSON_STRING_COLOR),
+                Value::Number(number) => (number.to_string(), JSON_NUMBER_COLOR),
+                other => (other.to_string(), JSON_LITERAL_COLOR),
+            };
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 0.;
+                ui.colored_label(JSON_KEY_COLOR, key);
+                ui.add(
+                    egui::Label::new(egui::RichText::new(text).color(color).monospace())
+                        .sense(egui::Sense::click()),
+                )
+                .on_hover_text(path)
+                .context_menu(|ui| json_node_menu(ui, value, path));
+            });
+            return;
+        }
+    };
+    let summary = match value {
+        Value::Object(_) => format!("{{{}}}", children.len()),
+        _ => format!("[{}]", children.len()),
+    };
+    let title = egui::RichText::new(format!("{}{}", key, summary)).monospace();
+    CollapsingHeader::new(title)
+        .id_source(id.with(path))
+        .default_open(path == "$")
+        .show(ui, |ui| {
+            for (key, child_path, child) in children {
+                render_json_tree(ui, child, &child_path, &key, id);
+            }
+        })
+        .header_response
+        .on_hover_text(path)
+        .context_menu(|ui| json_node_menu(ui, value, path));
+}
+
+fn json_node_menu(ui: &mut egui::Ui, value: &Value, path: &str) {
+    if ui.button("Copy value").clicked() {
+        let text = match value {
+            Value::String(text) => text.clone(),
+            other => serde_json::to_string_pretty(other).unwrap_or_default(),
+        };
+        ui.output_mut(|o| o.copied_text = text);
+        ui.close_menu();
+    }
+    if ui.button("Copy JSON path").clicked() {
+        ui.output_mut(|o| o.copied_text = path.to_string());
+        ui.close_menu();
+    }
+}
+
+fn render_variables(
+    ui: &mut egui::Ui,
+    state: &mut std::sync::MutexGuard<'_, AppState>,
+    window_index: usize,
+) {
+    let window = &mut state.connections[window_index];
+    CollapsingHeader::new(format!("Variables ({})", window.connection.variables.len()))
+        .id_source(("variables", window.id))
+        .show(ui, |ui| {
+            let mut variables: Vec<_> = window.connection.variables.iter().collect();
+            variables.sort();
+            egui::Grid::new(("variables_grid", window.id))
+                .striped(true)
+                .show(ui, |ui| {
+                    for (name, value) in variables {
+                        ui.label(name);
+                        ui.add(egui::Label::new(value).wrap(true));
+                        ui.end_row();
+                    }
+                });
+
+            ui.separator();
+            ui.label("Extraction Rules:");
+            let mut rule_to_remove = None;
+            for (index, rule) in window.connection.extraction_rules.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.button("🗑").on_hover_text("Remove rule").clicked() {
+                        rule_to_remove = Some(index);
+                    }
+                    let kind = match rule.kind {
+                        ExtractionKind::JsonPath => "JSONPath",
+                        ExtractionKind::Regex => "Regex",
+                    };
+                    ui.label(format!("{} <- {} {}", rule.variable, kind, rule.expression));
+                });
+            }
+            if let Some(index) = rule_to_remove {
+                window.connection.extraction_rules.remove(index);
+            }
+
+            if let Some(error) = &window.rule_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            ui.horizontal(|ui| {
+                ui.label("Variable:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut window.editing_rule.variable)
+                        .desired_width(80.),
+                );
+                ui.radio_value(
+                    &mut window.editing_rule.kind,
+                    ExtractionKind::JsonPath,
+                    "JSONPath",
+                );
+                ui.radio_value(
+                    &mut window.editing_rule.kind,
+                    ExtractionKind::Regex,
+                    "Regex",
+                );
+                ui.text_edit_singleline(&mut window.editing_rule.expression);
+                if ui.button("Add Rule").clicked() {
+                    match validate_rule(&window.editing_rule) {
+                        Ok(()) => {
+                            let rule = std::mem::take(&mut window.editing_rule);
+                            window.connection.extraction_rules.push(rule);
+                            window.rule_error = None;
+                        }
+                        Err(e) => window.rule_error = Some(e.to_string()),
+                    }
+                }
+            });
+        });
+}
+
+fn render_scenario(
+    ui: &mut egui::Ui,
+    state: &mut std::sync::MutexGuard<'_, AppState>,
+    window_index: usize,
+    actions: &mut Vec<WindowAction>,
+    ui_to_network: &Sender<Message>,
+) {
+    let window_id = state.connections[window_index].id;
+    CollapsingHeader::new("Scenario")
+        .id_source(("scenario", window_id))
+        .show(ui, |ui| {
+            let mut run_on = Vec::new();
+            ui.horizontal(|ui| {
+                ui.label("File:");
+                ui.text_edit_singleline(&mut state.connections[window_index].scenario_path);
+                if ui.button("Run").clicked() {
+                    run_on.push(window_index);
+                }
+                if ui
+                    .button("Run on all")
+                    .on_hover_text("Run the scenario on every connection")
+                    .clicked()
+                {
+                    run_on.extend(0..state.connections.len());
+                }
+            });
+
+            if !run_on.is_empty() {
+                let path = state.connections[window_index].scenario_path.clone();
+                let scenario = std::fs::read_to_string(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|text| parse_scenario(&path, &text));
+                match scenario {
+                    Ok(scenario) => {
+                        state.connections[window_index].scenario_error = None;
+                        for index in run_on {
+                            let window = &mut state.connections[index];
+                            window.scenario_run = Some(ScenarioRun {
+                                name: scenario.name.clone(),
+                                lines: scenario.lines(),
+                                results: Vec::new(),
+                                passed: None,
+                            });
+                            actions.push(WindowAction::Send(
+                                ui_to_network.clone(),
+                                Message::RunScenario {
+                                    id: window.id,
+                                    ip: render_url(&window.connection.url),
+                                    scenario: scenario.clone(),
+                                },
+                            ));
+                        }
+                    }
+                    Err(e) => state.connections[window_index].scenario_error = Some(e.to_string()),
+                }
+            }
+
+            let window = &state.connections[window_index];
+            if let Some(error) = &window.scenario_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            if let Some(run) = &window.scenario_run {
+                ui.label(format!("Scenario: {}", run.name));
+                for (line, source) in &run.lines {
+                    let latest = run.results.iter().rev().find(|r| r.line == *line);
+                    let (icon, color) = match latest {
+                        Some(result) if result.failure.is_some() => ("❌", egui::Color32::RED),
+                        Some(_) => ("✔", egui::Color32::GREEN),
+                        None => ("·", egui::Color32::GRAY),
+                    };
+                    let label =
+                        ui.colored_label(color, format!("{} {:>3}  {}", icon, line, source));
+                    if let Some(result) = latest {
+                        label.on_hover_text(format!(
+                            "{} ms\nReceived: {:?}",
+                            result.duration.as_millis(),
+                            result.frames
+                        ));
+                    }
+                }
+                match run.passed {
+                    None => {
+                        ui.label("Running...");
+                    }
+                    Some(true) => {
+                        ui.colored_label(egui::Color32::GREEN, "Scenario passed");
+                    }
+                    Some(false) => {
+                        let failure = run.results.iter().rev().find_map(|r| r.failure.as_ref());
+                        ui.colored_label(
+                            egui::Color32::RED,
+                            failure.map_or("Scenario failed", String::as_str),
+                        );
+                    }
+                }
+            }
+        });
+}
+
+fn render_script(
+    ui: &mut egui::Ui,
+    state: &mut std::sync::MutexGuard<'_, AppState>,
+    window_index: usize,
+    actions: &mut Vec<WindowAction>,
+    ui_to_network: &Sender<Message>,
+) {
+    let window = &mut state.connections[window_index];
+    let title = if window.connection.script_attached {
+        "Script (attached)"
+    } else {
+        "Script"
+    };
+    CollapsingHeader::new(title)
+        .id_source(("script", window.id))
+        .show(ui, |ui| {
+            ui.label("Hooks: on_open(), on_message(msg), on_tick(), on_close()")
+                .on_hover_text("Available: send(text), log(text), assert(cond, msg), set_var(name, value), get_var(name)");
+            ui.add(
+                egui::TextEdit::multiline(&mut window.connection.script)
+                    .code_editor()
+                    .desired_rows(8)
+                    .desired_width(f32::INFINITY),
+            );
+            ui.horizontal(|ui| {
+                let attach_label = if window.connection.script_enabled {
+                    "Reload"
+                } else {
+                    "Attach"
+                };
+                if ui.button(attach_label).clicked() {
+                    // Attached once the network task compiled it
+                    window.connection.script_enabled = true;
+                    actions.push(WindowAction::Send(
+                        ui_to_network.clone(),
+                        Message::SetScript {
+                            id: window.id,
+                            source: Some(window.connection.script.clone()),
+                        },
+                    ));
+                }
+                if window.connection.script_enabled && ui.button("Detach").clicked() {
+                    window.connection.script_enabled = false;
+                    window.connection.script_attached = false;
+                    actions.push(WindowAction::Send(
+                        ui_to_network.clone(),
+                        Message::SetScript {
+                            id: window.id,
+                            source: None,
+                        },
+                    ));
+                }
+            });
+        });
+}
+
+fn render_auto_responders(
+    ui: &mut egui::Ui,
+    state: &mut std::sync::MutexGuard<'_, AppState>,
+    window_index: usize,
+    actions: &mut Vec<WindowAction>,
+    ui_to_network: &Sender<Message>,
+) {
+    let window = &mut state.connections[window_index];
+    CollapsingHeader::new(format!(
+        "Auto-responders ({})",
+        window.connection.auto_responders.len()
+    ))
+    .id_source(("auto_responders", window.id))
+    .show(ui, |ui| {
+        let mut changed = false;
+        let mut responder_to_remove = None;
+        for (index, responder) in window.connection.auto_responders.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.button("🗑").on_hover_text("Remove responder").clicked() {
+                    responder_to_remove = Some(index);
+                }
+                ui.label(format!(
+                    "{:?} {} -> {} (after {} ms)",
+                    responder.kind, responder.pattern, responder.response, responder.delay_ms
+                ));
+            });
+        }
+        if let Some(index) = responder_to_remove {
+            window.connection.auto_responders.remove(index);
+            changed = true;
+        }
+
+        if let Some(error) = &window.responder_error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+        let editing = &mut window.editing_responder;
+        ui.horizontal(|ui| {
+            ui.label("When:");
+            ui.radio_value(&mut editing.kind, MatchKind::Contains, "Contains");
+            ui.radio_value(&mut editing.kind, MatchKind::Regex, "Regex");
+            ui.radio_value(&mut editing.kind, MatchKind::Json, "JSON");
+            ui.text_edit_singleline(&mut editing.pattern);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Send:");
+            ui.text_edit_singleline(&mut editing.response)
+                .on_hover_text(TEMPLATE_HINT);
+            ui.label("Delay (ms):");
+            ui.add(egui::DragValue::new(&mut editing.delay_ms));
+            if ui.button("Add Responder").clicked() {
+                match validate_responder(editing) {
+                    Ok(()) => {
+                        let responder = std::mem::take(editing);
+                        window.connection.auto_responders.push(responder);
+                        window.responder_error = None;
+                        changed = true;
+                    }
+                    Err(e) => window.responder_error = Some(e.to_string()),
+                }
+            }
+        });
+
+        if changed {
+            actions.push(WindowAction::Send(
+                ui_to_network.clone(),
+                Message::SetAutoResponders {
+                    id: window.id,
+                    responders: window.connection.auto_responders.clone(),
+                },
+            ));
+        }
+    });
+}
+
+fn render_codec(
+    ui: &mut egui::Ui,
+    state: &mut std::sync::MutexGuard<'_, AppState>,
+    window_index: usize,
+) {
+    let window = &mut state.connections[window_index];
+    let title = match &window.codec {
+        Some(codec) => format!("Payload Codec ({})", codec.name()),
+        None => String::from("Payload Codec"),
+    };
+    CollapsingHeader::new(title)
+        .id_source(("codec", window.id))
+        .show(ui, |ui| {
+            let settings = &mut window.codec_settings;
+            let mut apply = false;
+            ui.horizontal(|ui| {
+                for (kind, label) in [
+                    (CodecKind::None, "None"),
+                    (CodecKind::MessagePack, "MessagePack"),
+                    (CodecKind::Cbor, "CBOR"),
+                    (CodecKind::Protobuf, "Protobuf"),
+                ] {
+                    apply |= ui.radio_value(&mut settings.kind, kind, label).changed();
+                }
+            });
+            if settings.kind == CodecKind::Protobuf {
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut settings.proto_path)
+                        .on_hover_text(
+                            "A .proto file, or a descriptor set written by \
+                             protoc --descriptor_set_out",
+                        );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Message type:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut settings.message_type)
+                            .hint_text("package.Message"),
+                    );
+                    apply |= ui.button("Load").clicked();
+                });
+            }
+            if apply {
+                window.apply_codec();
+            }
+            if let Some(error) = &window.codec_error {
+                ui.colored_label(egui::Color32::RED, error);
+            } else if window.codec.is_some() {
+                ui.label("Binary frames are shown as JSON, messages sent are encoded from JSON");
+            }
+        });
+}
@@ -1,4 +1,8 @@
+use std::ops::Range;
+
+use rand::Rng;
 use regex::Regex;
+use serde_json::Value;
 
 pub fn is_valid_websocket_ip(ip: &str) -> bool {
     let re = Regex::new(
@@ -7,3 +11,108 @@ pub fn is_valid_websocket_ip(ip: &str) -> bool {
     .unwrap();
     re.is_match(ip)
 }
+
+/// Random printable payload used for fuzzing, between `min_len` and `max_len` characters.
+pub fn random_payload(min_len: usize, max_len: usize) -> String {
+    let printable: Vec<u8> = (b' '..=b'~').collect();
+    random_text(min_len, max_len, &printable)
+}
+
+/// Between `min_len` and `max_len` characters picked from the ASCII `alphabet`.
+pub fn random_text(min_len: usize, max_len: usize, alphabet: &[u8]) -> String {
+    let mut rng = rand::thread_rng();
+    let len = rng.gen_range(min_len..=max_len.max(min_len));
+    (0..len)
+        .map(|_| char::from(alphabet[rng.gen_range(0..alphabet.len())]))
+        .collect()
+}
+
+/// Bytes of hex digits such as `de ad be ef`, whitespace between digits is ignored.
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let digits: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Bytes as space separated hex digits, the format `parse_hex` reads.
+pub fn to_hex(data: &[u8]) -> String {
+    to_hex_hiding(data, &[])
+}
+
+/// `to_hex` with the bytes in the `hidden` ranges shown as `**`.
+pub fn to_hex_hiding(data: &[u8], hidden: &[Range<usize>]) -> String {
+    data.iter()
+        .enumerate()
+        .map(
+            |(index, byte)| match hidden.iter().any(|r| r.contains(&index)) {
+                true => String::from("**"),
+                false => format!("{:02x}", byte),
+            },
+        )
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `data` as lines of 16 bytes: offset, hex digits and the printable ASCII characters. The
+/// bytes in the `hidden` ranges are shown as `**` and `*`.
+pub fn hex_dump(data: &[u8], hidden: &[Range<usize>]) -> String {
+    let is_hidden = |index: usize| hidden.iter().any(|r| r.contains(&index));
+    data.chunks(16)
+        .enumerate()
+        .map(|(line, chunk)| {
+            let offset = line * 16;
+            let hex = chunk
+                .iter()
+                .enumerate()
+                .map(|(index, byte)| match is_hidden(offset + index) {
+                    true => String::from("**"),
+                    false => format!("{:02x}", byte),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii: String = chunk
+                .iter()
+                .enumerate()
+                .map(|(index, &byte)| match byte {
+                    _ if is_hidden(offset + index) => '*',
+                    b' '..=b'~' => char::from(byte),
+                    _ => '.',
+                })
+                .collect();
+            format!("{:08x}  {:<47}  {}", offset, hex, ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// JSONPath of the member `key` of the value at `path`.
+pub fn json_path_key(path: &str, key: &str) -> String {
+    let is_identifier = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if is_identifier {
+        format!("{}.{}", path, key)
+    } else {
+        format!(
+            "{}['{}']",
+            path,
+            key.replace('\\', "\\\\").replace('\'', "\\'")
+        )
+    }
+}
+
+/// JSON objects and arrays, other values are not worth a viewer.
+pub fn parse_json(text: &str) -> Option<Value> {
+    let trimmed = text.trim_start();
+    if !trimmed.starts_with('{') && !trimmed.starts_with('[') {
+        return None;
+    }
+    serde_json::from_str(text).ok()
+}
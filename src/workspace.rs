@@ -0,0 +1,51 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::autoresponder::validate_responder;
+use crate::extraction::validate_rule;
+use crate::structs::{AppState, Workspace};
+use crate::utils::is_valid_websocket_ip;
+
+/// Writes `workspace` to a single JSON file that teammates can import.
+pub fn export_workspace(workspace: &Workspace, path: &Path) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(workspace)?)?;
+    Ok(())
+}
+
+/// Reads a workspace exported with `export_workspace`, checking its profiles like the GUI
+/// checks them when they are edited.
+pub fn import_workspace(path: &Path) -> Result<Workspace> {
+    let workspace: Workspace = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if workspace.name.trim().is_empty() {
+        bail!("The workspace has no name");
+    }
+    for profile in &workspace.profiles {
+        let context = |e: anyhow::Error| anyhow!("Profile {}: {}", profile.name, e);
+        // URLs using the environment, e.g. {{env.BASE_URL}}/chat, are checked when connecting
+        if !profile.url.contains("{{") && !is_valid_websocket_ip(&profile.url) {
+            bail!(
+                "Profile {}: invalid WebSocket URL {}",
+                profile.name,
+                profile.url
+            );
+        }
+        for rule in &profile.extraction_rules {
+            validate_rule(rule).map_err(context)?;
+        }
+        for responder in &profile.auto_responders {
+            validate_responder(responder).map_err(context)?;
+        }
+    }
+    Ok(workspace)
+}
+
+/// Adds an imported workspace, replacing the one with the same name. Returns whether one was
+/// replaced.
+pub fn add_workspace(state: &mut AppState, workspace: Workspace) -> bool {
+    let replaced = state.workspaces.iter().any(|w| w.name == workspace.name);
+    state.workspaces.retain(|w| w.name != workspace.name);
+    state.workspaces.push(workspace);
+    replaced
+}